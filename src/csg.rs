@@ -0,0 +1,125 @@
+use crate::figure::{Exact, Range, Relation};
+use crate::spec::{Quadrant, Shape};
+use serde_derive::{Deserialize, Serialize};
+
+use Relation::*;
+
+// Three-valued set operations. A point inside either operand is inside the
+// union even if it is on the other's border; it is on the union's border only
+// if it is on a border and inside neither. Intersection is the dual, and the
+// complement keeps borders where they are.
+pub fn union(a: Relation, b: Relation) -> Relation {
+    match (a, b) {
+        (Inside, _) | (_, Inside) => Inside,
+        (Border, _) | (_, Border) => Border,
+        (Outside, Outside) => Outside,
+    }
+}
+
+pub fn intersection(a: Relation, b: Relation) -> Relation {
+    match (a, b) {
+        (Outside, _) | (_, Outside) => Outside,
+        (Border, _) | (_, Border) => Border,
+        (Inside, Inside) => Inside,
+    }
+}
+
+pub fn complement(a: Relation) -> Relation {
+    match a {
+        Inside => Outside,
+        Border => Border,
+        Outside => Inside,
+    }
+}
+
+pub fn difference(a: Relation, b: Relation) -> Relation {
+    intersection(a, complement(b))
+}
+
+// `{ shape = "circle", border = 20 }` is a primitive, `{ quadrant =
+// "upper_right" }` the quadrant `Quadrant::of` picks (with no border of its
+// own), and the rest combine regions: `difference` takes everything after
+// the first region away from it.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum Region {
+    Quadrant(Quadrant),
+    Union(Vec<Region>),
+    Intersection(Vec<Region>),
+    Difference(Vec<Region>),
+    Complement(Box<Region>),
+    #[serde(untagged)]
+    Shape {
+        shape: Shape,
+        border: i32,
+    },
+}
+
+impl Region {
+    pub fn relation<T: Exact + Default>(&self, x: T, y: T) -> Relation {
+        match self {
+            Region::Shape { shape, border } => shape.relation(x, y, T::from(*border)),
+            Region::Quadrant(quadrant) if Quadrant::of(x, y) == *quadrant => Inside,
+            Region::Quadrant(_) => Outside,
+            Region::Union(regions) => regions
+                .iter()
+                .fold(Outside, |acc, r| union(acc, r.relation(x, y))),
+            Region::Intersection(regions) => regions
+                .iter()
+                .fold(Inside, |acc, r| intersection(acc, r.relation(x, y))),
+            Region::Difference(regions) => match regions.split_first() {
+                Some((first, rest)) => rest.iter().fold(first.relation(x, y), |acc, r| {
+                    difference(acc, r.relation(x, y))
+                }),
+                None => Outside,
+            },
+            Region::Complement(region) => complement(region.relation(x, y)),
+        }
+    }
+
+    fn children(&self) -> &[Region] {
+        match self {
+            Region::Union(regions)
+            | Region::Intersection(regions)
+            | Region::Difference(regions) => regions,
+            Region::Complement(region) => std::slice::from_ref(region.as_ref()),
+            Region::Shape { .. } | Region::Quadrant(_) => &[],
+        }
+    }
+
+    pub fn check(&self, reach: i128) -> Result<(), String> {
+        match self {
+            Region::Shape { shape, border } => {
+                if *border < 0 {
+                    return Err("borders must not be negative".to_string());
+                }
+                shape.check()?;
+                let reach = reach.max(i128::from(*border));
+                if !shape.fits(reach) {
+                    return Err("shapes overflow exact arithmetic over its range".to_string());
+                }
+            }
+            Region::Union(regions)
+            | Region::Intersection(regions)
+            | Region::Difference(regions)
+                if regions.is_empty() =>
+            {
+                return Err("`union`, `intersection` and `difference` need a region".to_string());
+            }
+            _ => {}
+        }
+        self.children()
+            .iter()
+            .try_for_each(|region| region.check(reach))
+    }
+}
+
+// A figure given by a single region rather than by quadrant bands.
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Composite {
+    pub name: String,
+    #[serde(default)]
+    pub range: Range,
+    pub region: Region,
+}
@@ -0,0 +1,612 @@
+use crate::figure::Relation;
+use crate::polygon::Polygon;
+use crate::ratio::Ratio;
+use crate::spec::{Axes, Band, FigureSpec, Part, Shape};
+use crate::stats::{in_part, parts, radial};
+use serde_derive::Serialize;
+use std::f64::consts::FRAC_PI_2;
+
+// Exact squared distances are only formed when the shape's values for the
+// point stay below this, so that squaring them cannot overflow.
+const MAX_EXACT: i128 = 1 << 60;
+
+const CURVE_SAMPLES: usize = 1024;
+const CURVE_REFINEMENTS: usize = 60;
+const EDGE_SAMPLES: usize = 64;
+
+// Euclidean distance from a point to the border of one shape, unsigned.
+// `squared` is exact wherever the squared distance is rational: for boxes,
+// diamonds and polygons, and for circles and rotated boxes whose lengths
+// happen to be integers. Ellipses and other `lp` shapes only have `float`.
+pub struct ToBorder {
+    pub float: f64,
+    pub squared: Option<Ratio>,
+}
+
+impl ToBorder {
+    fn exact(squared: Ratio) -> Self {
+        ToBorder {
+            float: (squared.num() as f64 / squared.den() as f64).sqrt(),
+            squared: Some(squared),
+        }
+    }
+
+    fn float(float: f64) -> Self {
+        ToBorder {
+            float,
+            squared: None,
+        }
+    }
+}
+
+pub fn to_border(shape: &Shape, x: i32, y: i32, border: i32) -> ToBorder {
+    let (xi, yi, r) = (i128::from(x), i128::from(y), i128::from(border));
+    let reach = xi.abs().max(yi.abs()).max(r);
+    let exact = shape.magnitude(reach).is_some_and(|m| m <= MAX_EXACT);
+    let (xf, yf, rf) = (f64::from(x), f64::from(y), f64::from(border));
+    match shape {
+        Shape::Box if exact => ToBorder::exact(Ratio::from(box_squared(xi, yi, r))),
+        Shape::Diamond | Shape::Lp(1) if exact => ToBorder::exact(diamond_squared(xi, yi, r)),
+        Shape::Circle | Shape::Lp(2) if exact => circle(xi, yi, r),
+        Shape::Ellipse([a, b]) if a == b && exact => circle(xi, yi, r * i128::from(*a)),
+        Shape::RotatedBox([c, s]) if exact => rotated_box(xi, yi, r, (*c, *s))
+            .unwrap_or_else(|| ToBorder::float(rotated_box_f64(xf, yf, rf, (*c, *s)))),
+        Shape::Polygon(polygon) if exact => ToBorder::exact(polygon_squared(polygon, xi, yi, r)),
+        _ => ToBorder::float(to_border_f64(shape, xf, yf, rf)),
+    }
+}
+
+pub fn to_border_f64(shape: &Shape, x: f64, y: f64, border: f64) -> f64 {
+    match shape {
+        Shape::Box => box_f64(x, y, border),
+        Shape::Circle | Shape::Lp(2) => (x.hypot(y) - border).abs(),
+        Shape::Diamond | Shape::Lp(1) => diamond_f64(x, y, border),
+        Shape::Lp(_) | Shape::Ellipse(_) => {
+            let (nx, ny) = nearest_f64(shape, x, y, border);
+            (x - nx).hypot(y - ny)
+        }
+        Shape::RotatedBox(side) => rotated_box_f64(x, y, border, (side[0], side[1])),
+        Shape::Polygon(polygon) => {
+            let points = polygon.points(border);
+            (0..points.len())
+                .map(|i| segment_f64((x, y), points[i], points[(i + 1) % points.len()]))
+                .fold(f64::INFINITY, f64::min)
+        }
+    }
+}
+
+// The point of the shape's border nearest to `(x, y)`; from the centre
+// itself, the first one counter-clockwise from the positive x axis.
+pub fn nearest_f64(shape: &Shape, x: f64, y: f64, border: f64) -> (f64, f64) {
+    match shape {
+        Shape::Box => box_nearest(x, y, border),
+        Shape::Circle | Shape::Lp(2) => {
+            let length = x.hypot(y);
+            if length == 0.0 {
+                (border, 0.0)
+            } else {
+                (x * border / length, y * border / length)
+            }
+        }
+        Shape::Diamond | Shape::Lp(1) => {
+            let (a, b) = (x.abs(), y.abs());
+            let (na, nb) = if a - b > border {
+                (border, 0.0)
+            } else if b - a > border {
+                (0.0, border)
+            } else {
+                ((a - b + border) / 2.0, (b - a + border) / 2.0)
+            };
+            (na.copysign(x), nb.copysign(y))
+        }
+        Shape::Lp(p) => {
+            let e = 2.0 / f64::from(*p);
+            curve_f64(x, y, |t| {
+                (border * t.cos().powf(e), border * t.sin().powf(e))
+            })
+        }
+        Shape::Ellipse([a, b]) => {
+            let (a, b) = (border * f64::from(*a), border * f64::from(*b));
+            curve_f64(x, y, |t| (a * t.cos(), b * t.sin()))
+        }
+        Shape::RotatedBox([c, s]) => {
+            let (c, s) = (f64::from(*c), f64::from(*s));
+            let n = c.hypot(s);
+            let (u, v) = box_nearest((c * x + s * y) / n, (c * y - s * x) / n, border);
+            ((c * u - s * v) / n, (s * u + c * v) / n)
+        }
+        Shape::Polygon(polygon) => {
+            let points = polygon.points(border);
+            (0..points.len())
+                .map(|i| segment_nearest((x, y), points[i], points[(i + 1) % points.len()]))
+                .min_by(|p, q| {
+                    (x - p.0)
+                        .hypot(y - p.1)
+                        .total_cmp(&(x - q.0).hypot(y - q.1))
+                })
+                .expect("a polygon has vertices")
+        }
+    }
+}
+
+fn box_squared(x: i128, y: i128, r: i128) -> i128 {
+    let (a, b) = (x.abs(), y.abs());
+    if a.max(b) <= r {
+        (r - a.max(b)).pow(2)
+    } else {
+        (a - r).max(0).pow(2) + (b - r).max(0).pow(2)
+    }
+}
+
+fn box_f64(x: f64, y: f64, r: f64) -> f64 {
+    let (a, b) = (x.abs(), y.abs());
+    if a.max(b) <= r {
+        r - a.max(b)
+    } else {
+        (a - r).max(0.0).hypot((b - r).max(0.0))
+    }
+}
+
+// Inside, the nearer of the two edges the point faces is moved out to;
+// outside, the point is clamped onto the box.
+fn box_nearest(x: f64, y: f64, r: f64) -> (f64, f64) {
+    if x.abs().max(y.abs()) > r {
+        (x.clamp(-r, r), y.clamp(-r, r))
+    } else if x.abs() >= y.abs() {
+        (r.copysign(x), y)
+    } else {
+        (x, r.copysign(y))
+    }
+}
+
+// Inside, and outside opposite an edge, the nearest point is on the edge
+// `|x| + |y| = r`; beyond the ends of the edge it is a corner.
+fn diamond_squared(x: i128, y: i128, r: i128) -> Ratio {
+    let (a, b) = (x.abs(), y.abs());
+    if a - b > r {
+        Ratio::from((a - r).pow(2) + b.pow(2))
+    } else if b - a > r {
+        Ratio::from(a.pow(2) + (b - r).pow(2))
+    } else {
+        Ratio::new((a + b - r).pow(2), 2)
+    }
+}
+
+fn diamond_f64(x: f64, y: f64, r: f64) -> f64 {
+    let (a, b) = (x.abs(), y.abs());
+    if a - b > r {
+        (a - r).hypot(b)
+    } else if b - a > r {
+        a.hypot(b - r)
+    } else {
+        (a + b - r).abs() / 2f64.sqrt()
+    }
+}
+
+// Exact when the point's own distance from the centre is an integer.
+fn circle(x: i128, y: i128, r: i128) -> ToBorder {
+    let squared = x * x + y * y;
+    let root = squared.isqrt();
+    if root * root == squared {
+        ToBorder::exact(Ratio::from((root - r).pow(2)))
+    } else {
+        ToBorder::float(((squared as f64).sqrt() - r as f64).abs())
+    }
+}
+
+// In the turned frame of `rotated_box_distance` everything is `n` times too
+// long, which only cancels exactly when `n` is an integer.
+fn rotated_box(x: i128, y: i128, r: i128, (c, s): (i32, i32)) -> Option<ToBorder> {
+    let (c, s) = (i128::from(c), i128::from(s));
+    let norm = c * c + s * s;
+    let n = norm.isqrt();
+    if n * n != norm {
+        return None;
+    }
+    let (u, v) = (c * x + s * y, c * y - s * x);
+    Some(ToBorder::exact(Ratio::new(box_squared(u, v, r * n), norm)))
+}
+
+fn rotated_box_f64(x: f64, y: f64, r: f64, (c, s): (i32, i32)) -> f64 {
+    let (c, s) = (f64::from(c), f64::from(s));
+    let n = c.hypot(s);
+    box_f64((c * x + s * y) / n, (c * y - s * x) / n, r)
+}
+
+// Squared distance to the nearest edge, in the polygon's lattice scaled by
+// `scale` and then brought back.
+fn polygon_squared(polygon: &Polygon, x: i128, y: i128, r: i128) -> Ratio {
+    let scale = polygon.scale();
+    let p = (x * scale, y * scale);
+    let vertices: Vec<(i128, i128)> = polygon
+        .lattice()
+        .iter()
+        .map(|&(vx, vy)| (vx * r, vy * r))
+        .collect();
+    let nearest = (0..vertices.len())
+        .map(|i| segment_squared(p, vertices[i], vertices[(i + 1) % vertices.len()]))
+        .min()
+        .expect("a polygon has vertices");
+    nearest * Ratio::new(1, scale * scale)
+}
+
+fn segment_squared(p: (i128, i128), a: (i128, i128), b: (i128, i128)) -> Ratio {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let (wx, wy) = (p.0 - a.0, p.1 - a.1);
+    let along = wx * dx + wy * dy;
+    let length = dx * dx + dy * dy;
+    if along <= 0 || length == 0 {
+        Ratio::from(wx * wx + wy * wy)
+    } else if along >= length {
+        Ratio::from((p.0 - b.0).pow(2) + (p.1 - b.1).pow(2))
+    } else {
+        Ratio::new((dx * wy - dy * wx).pow(2), length)
+    }
+}
+
+fn segment_f64(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (nx, ny) = segment_nearest(p, a, b);
+    (p.0 - nx).hypot(p.1 - ny)
+}
+
+fn segment_nearest(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx * dx + dy * dy;
+    let t = if length > 0.0 {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (a.0 + t * dx, a.1 + t * dy)
+}
+
+// Shapes symmetric in both axes have the nearest border point in the
+// point's own quadrant, so the curve is only walked from `t = 0` to `pi / 2`:
+// sampled, then narrowed around the best sample by ternary search.
+fn curve_f64(x: f64, y: f64, curve: impl Fn(f64) -> (f64, f64)) -> (f64, f64) {
+    let (a, b) = (x.abs(), y.abs());
+    let gap = |t: f64| {
+        let (cx, cy) = curve(t);
+        (cx - a).hypot(cy - b)
+    };
+    let step = FRAC_PI_2 / CURVE_SAMPLES as f64;
+    let best = (0..=CURVE_SAMPLES)
+        .map(|i| i as f64 * step)
+        .min_by(|&s, &t| gap(s).total_cmp(&gap(t)))
+        .unwrap_or_default();
+    let (mut lo, mut hi) = ((best - step).max(0.0), (best + step).min(FRAC_PI_2));
+    for _ in 0..CURVE_REFINEMENTS {
+        let (m1, m2) = (lo + (hi - lo) / 3.0, hi - (hi - lo) / 3.0);
+        if gap(m1) < gap(m2) {
+            hi = m2;
+        } else {
+            lo = m1;
+        }
+    }
+    let (cx, cy) = curve((lo + hi) / 2.0);
+    (cx.copysign(x), cy.copysign(y))
+}
+
+// Whether the piece of border `k` at `point` shows, i.e. lies outside every
+// border before it, as `stats` counts border length.
+fn shown(spec: &FigureSpec, band: &Band, k: usize, (x, y): (f64, f64), eps: f64) -> bool {
+    (0..k).all(|j| {
+        let relation = band.shape(j).relation_f64(x, y, spec.border(j).into(), eps);
+        matches!(relation, Relation::Outside)
+    })
+}
+
+// The point nearest to `p` of the curve `at(t)` for `t` in `lo..=hi`, among
+// those `keep` accepts: sampled, narrowed around the best sample by ternary
+// search, and with the ends of every accepted run found by bisection.
+fn clipped(
+    p: (f64, f64),
+    (lo, hi): (f64, f64),
+    samples: usize,
+    at: impl Fn(f64) -> (f64, f64),
+    keep: impl Fn((f64, f64)) -> bool,
+) -> Option<(f64, f64)> {
+    let gap = |q: (f64, f64)| (p.0 - q.0).hypot(p.1 - q.1);
+    let step = (hi - lo) / samples as f64;
+    let ts: Vec<f64> = (0..=samples)
+        .map(|i| {
+            if i == samples {
+                hi
+            } else {
+                lo + i as f64 * step
+            }
+        })
+        .collect();
+    let points: Vec<(f64, f64)> = ts.iter().map(|&t| at(t)).collect();
+    let kept: Vec<bool> = points.iter().map(|&q| keep(q)).collect();
+
+    let mut found = Vec::new();
+    let best = (0..=samples)
+        .filter(|&i| kept[i])
+        .min_by(|&i, &j| gap(points[i]).total_cmp(&gap(points[j])));
+    if let Some(best) = best {
+        found.push(points[best]);
+        let score = |t: f64| {
+            let q = at(t);
+            if keep(q) {
+                gap(q)
+            } else {
+                f64::INFINITY
+            }
+        };
+        let (mut lo, mut hi) = (ts[best.saturating_sub(1)], ts[(best + 1).min(samples)]);
+        for _ in 0..CURVE_REFINEMENTS {
+            let (m1, m2) = (lo + (hi - lo) / 3.0, hi - (hi - lo) / 3.0);
+            if score(m1) < score(m2) {
+                hi = m2;
+            } else {
+                lo = m1;
+            }
+        }
+        found.push(at((lo + hi) / 2.0));
+    }
+    for i in 0..samples {
+        if kept[i] != kept[i + 1] {
+            let (mut yes, mut no) = if kept[i] {
+                (ts[i], ts[i + 1])
+            } else {
+                (ts[i + 1], ts[i])
+            };
+            for _ in 0..CURVE_REFINEMENTS {
+                let mid = (yes + no) / 2.0;
+                if keep(at(mid)) {
+                    yes = mid;
+                } else {
+                    no = mid;
+                }
+            }
+            found.push(at(yes));
+        }
+    }
+    found
+        .into_iter()
+        .filter(|&q| keep(q))
+        .min_by(|&q, &r| gap(q).total_cmp(&gap(r)))
+}
+
+// A point of the figure's outline: on border `border` of `part`, tested by
+// `function`, or on a seam between two parts when `border` is `None`.
+// `exact` is set when the point is the nearest of its whole shape, where
+// `to_border` applies.
+struct Found {
+    point: (f64, f64),
+    distance: f64,
+    part: Option<Part>,
+    border: Option<usize>,
+    function: Option<&'static str>,
+    exact: Option<ToBorder>,
+}
+
+// The outline is made of the pieces of every part's borders that lie in the
+// part and show (see `shown`), and of the pieces of the axes or rays between
+// two parts where one side is inside and the other is not. The nearest point
+// of a whole shape is taken as is when it lies on such a piece; otherwise the
+// pieces are searched by `clipped`, and the closed form wins ties.
+fn outline_nearest(spec: &FigureSpec, x: i32, y: i32) -> Found {
+    let (xf, yf) = (f64::from(x), f64::from(y));
+    let eps = 1e-9 * f64::from(spec.max_border().max(1));
+    let gap = |q: (f64, f64)| (xf - q.0).hypot(yf - q.1);
+    let mut closed: Vec<Found> = Vec::new();
+    let mut searched: Vec<Found> = Vec::new();
+
+    for (part, band, span) in parts(spec) {
+        for k in 0..spec.border_count() {
+            let (shape, border) = (band.shape(k), spec.border(k));
+            let keep = |q: (f64, f64)| in_part(spec, part, q) && shown(spec, band, k, q, eps);
+            let found = |point: (f64, f64), exact: Option<ToBorder>| Found {
+                point,
+                distance: gap(point),
+                part: Some(part),
+                border: Some(k),
+                function: Some(shape.function()),
+                exact,
+            };
+            let point = nearest_f64(shape, xf, yf, border.into());
+            if keep(point) {
+                closed.push(found(point, Some(to_border(shape, x, y, border))));
+                continue;
+            }
+            let point = match shape {
+                Shape::Polygon(polygon) => {
+                    let points = polygon.points(border.into());
+                    (0..points.len())
+                        .filter_map(|i| {
+                            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+                            let at = |t: f64| (a.0 + t * (b.0 - a.0), a.1 + t * (b.1 - a.1));
+                            clipped((xf, yf), (0.0, 1.0), EDGE_SAMPLES, at, keep)
+                        })
+                        .min_by(|&q, &r| gap(q).total_cmp(&gap(r)))
+                }
+                _ => {
+                    let at = |t: f64| {
+                        let (s, c) = t.sin_cos();
+                        let rho = radial(shape, border.into(), (c, s))
+                            .first()
+                            .map_or(0.0, |range| range.1);
+                        (rho * c, rho * s)
+                    };
+                    let samples = ((span.1 - span.0) / FRAC_PI_2 * CURVE_SAMPLES as f64).ceil();
+                    clipped((xf, yf), span, samples as usize, at, keep)
+                }
+            };
+            searched.extend(point.map(|point| found(point, None)));
+        }
+    }
+
+    let inside = |band: &Band, (x, y): (f64, f64)| {
+        spec.band_ring_f64(band, x, y, eps).relation() == Relation::Inside
+    };
+    for (u, a, b) in seams(spec) {
+        let mut stops: Vec<f64> = [a, b]
+            .into_iter()
+            .flat_map(|band| band.shapes().zip(spec.borders()))
+            .flat_map(|(shape, border)| radial(shape, border.into(), u))
+            .flat_map(|(lo, hi)| [lo, hi])
+            .collect();
+        stops.push(0.0);
+        stops.sort_by(f64::total_cmp);
+        stops.dedup();
+        stops.push(f64::INFINITY);
+        for pair in stops.windows(2) {
+            let (lo, hi) = (pair[0], pair[1]);
+            let mid = if hi.is_finite() {
+                (lo + hi) / 2.0
+            } else {
+                lo + 1.0
+            };
+            let (on_a, on_b) = (
+                inside(a, (mid * u.0, mid * u.1)),
+                inside(b, (mid * u.0, mid * u.1)),
+            );
+            let seam = on_a != on_b || (spec.axes == Axes::Border && on_a);
+            if seam {
+                let t = (xf * u.0 + yf * u.1).clamp(lo, hi);
+                let point = (t * u.0, t * u.1);
+                let part = spec
+                    .resolve(point.0, point.1, |band| {
+                        spec.band_ring_f64(band, point.0, point.1, eps)
+                    })
+                    .map(|(part, _, _)| part);
+                searched.push(Found {
+                    point,
+                    distance: gap(point),
+                    part,
+                    border: None,
+                    function: None,
+                    exact: None,
+                });
+            }
+        }
+    }
+
+    let nearest = |found: Vec<Found>| {
+        found
+            .into_iter()
+            .min_by(|f, g| f.distance.total_cmp(&g.distance))
+    };
+    match (nearest(closed), nearest(searched)) {
+        (Some(closed), Some(searched)) if searched.distance + eps < closed.distance => searched,
+        (Some(closed), _) => closed,
+        (None, searched) => searched.expect("a figure has at least two borders"),
+    }
+}
+
+// The directions of the axes or rays between two parts, with the bands on
+// either side.
+fn seams(spec: &FigureSpec) -> Vec<((f64, f64), &Band, &Band)> {
+    if let Some(q) = &spec.quadrants {
+        return vec![
+            ((1.0, 0.0), &q.upper_right, &q.lower_right),
+            ((0.0, 1.0), &q.upper_right, &q.upper_left),
+            ((-1.0, 0.0), &q.upper_left, &q.lower_left),
+            ((0.0, -1.0), &q.lower_left, &q.lower_right),
+        ];
+    }
+    let sectors = &spec.sectors;
+    if sectors.len() < 2 {
+        return vec![];
+    }
+    (0..sectors.len())
+        .map(|k| {
+            let (x, y) = sectors[k].from.direction();
+            let (x, y) = (f64::from(x), f64::from(y));
+            let n = x.hypot(y);
+            let before = &sectors[(k + sectors.len() - 1) % sectors.len()].band;
+            ((x / n, y / n), before, &sectors[k].band)
+        })
+        .collect()
+}
+
+// `distance` is negative inside the figure, zero on its border and positive
+// outside; `squared` is its exact square when that is known. The nearest
+// border is looked for along the whole outline (see `outline_nearest`).
+#[derive(Serialize)]
+pub struct Distance {
+    pub figure: String,
+    pub x: i32,
+    pub y: i32,
+    pub relation: Relation,
+    pub distance: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub squared: Option<Ratio>,
+}
+
+pub fn distance(spec: &FigureSpec, x: i32, y: i32) -> Distance {
+    let (xi, yi) = (i128::from(x), i128::from(y));
+    let resolved = spec.resolve(xi, yi, |band| spec.band_ring(band, xi, yi));
+    let relation = resolved.map_or(Relation::Border, |(_, _, ring)| ring.relation());
+    let nearest = match relation {
+        // on a border, or on an axis that `axes = "border"` makes border
+        Relation::Border => ToBorder::exact(Ratio::from(0)),
+        _ => {
+            let found = outline_nearest(spec, x, y);
+            found
+                .exact
+                .unwrap_or_else(|| ToBorder::float(found.distance))
+        }
+    };
+    let distance = match relation {
+        Relation::Inside => -nearest.float,
+        Relation::Border => 0.0,
+        Relation::Outside => nearest.float,
+    };
+    Distance {
+        figure: spec.name.clone(),
+        x,
+        y,
+        relation,
+        distance,
+        squared: nearest.squared,
+    }
+}
+
+// The point of the figure's outline nearest to `(x, y)`, for snapping onto
+// it, with the quadrant or sector it belongs to. On a border, `border` and
+// `function` tell which one and the shape that tests it; they are absent for
+// a point on an axis or ray between two parts. A point on an axis that
+// `axes = "border"` makes border is its own nearest point.
+#[derive(Serialize)]
+pub struct Nearest {
+    pub figure: String,
+    pub x: i32,
+    pub y: i32,
+    #[serde(flatten)]
+    pub part: Option<Part>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function: Option<&'static str>,
+    pub point: [f64; 2],
+    pub distance: f64,
+}
+
+pub fn nearest(spec: &FigureSpec, x: i32, y: i32) -> Nearest {
+    let (xi, yi) = (i128::from(x), i128::from(y));
+    let mut nearest = Nearest {
+        figure: spec.name.clone(),
+        x,
+        y,
+        part: None,
+        border: None,
+        function: None,
+        point: [f64::from(x), f64::from(y)],
+        distance: 0.0,
+    };
+    if spec
+        .resolve(xi, yi, |band| spec.band_ring(band, xi, yi))
+        .is_some()
+    {
+        let found = outline_nearest(spec, x, y);
+        nearest.part = found.part;
+        nearest.border = found.border;
+        nearest.function = found.function;
+        nearest.point = [found.point.0, found.point.1];
+        nearest.distance = found.distance;
+    }
+    nearest
+}
@@ -0,0 +1,118 @@
+use crate::figure::Bounds;
+use crate::negotiate;
+use serde_derive::Serialize;
+use warp::http::StatusCode;
+use warp::{reject::Reject, Rejection, Reply};
+
+#[derive(Debug, Serialize)]
+pub struct UnknownFigure {
+    pub error: &'static str,
+    pub figure: String,
+    pub known: Vec<String>,
+}
+
+impl UnknownFigure {
+    pub fn new(figure: String, known: Vec<String>) -> Self {
+        UnknownFigure {
+            error: "unknown_figure",
+            figure,
+            known,
+        }
+    }
+}
+
+impl Reject for UnknownFigure {}
+
+#[derive(Debug, Serialize)]
+pub struct NotAcceptable {
+    pub error: &'static str,
+    pub supported: Vec<&'static str>,
+}
+
+impl NotAcceptable {
+    pub fn new() -> Self {
+        NotAcceptable {
+            error: "not_acceptable",
+            supported: negotiate::supported(),
+        }
+    }
+}
+
+impl Reject for NotAcceptable {}
+
+// Composite and formula figures have no quadrants or sectors to explain,
+// measure or draw.
+#[derive(Debug, Serialize)]
+pub struct Unsupported {
+    pub error: &'static str,
+    pub figure: String,
+}
+
+impl Unsupported {
+    pub fn new(figure: &str) -> Self {
+        Unsupported {
+            error: "unsupported",
+            figure: figure.to_string(),
+        }
+    }
+}
+
+impl Reject for Unsupported {}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    OutOfRange,
+    UnknownField,
+    MissingField,
+    NotANumber,
+    InvalidValue,
+    TooManyCells,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldError {
+    pub error: ErrorCode,
+    pub field: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range: Option<Bounds>,
+}
+
+impl FieldError {
+    pub fn new(error: ErrorCode, field: &str) -> Self {
+        FieldError {
+            error,
+            field: field.to_string(),
+            range: None,
+        }
+    }
+
+    pub fn with_range(self, range: Bounds) -> Self {
+        FieldError {
+            range: Some(range),
+            ..self
+        }
+    }
+}
+
+impl Reject for FieldError {}
+
+pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Rejection> {
+    if let Some(e) = err.find::<UnknownFigure>() {
+        let json = warp::reply::json(e);
+        return Ok(warp::reply::with_status(json, StatusCode::NOT_FOUND));
+    }
+    if let Some(e) = err.find::<FieldError>() {
+        let json = warp::reply::json(e);
+        return Ok(warp::reply::with_status(json, StatusCode::BAD_REQUEST));
+    }
+    if let Some(e) = err.find::<NotAcceptable>() {
+        let json = warp::reply::json(e);
+        return Ok(warp::reply::with_status(json, StatusCode::NOT_ACCEPTABLE));
+    }
+    if let Some(e) = err.find::<Unsupported>() {
+        let json = warp::reply::json(e);
+        return Ok(warp::reply::with_status(json, StatusCode::NOT_IMPLEMENTED));
+    }
+    Err(err)
+}
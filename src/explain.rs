@@ -0,0 +1,110 @@
+use crate::figure::{distance_relation, ring, Relation, Ring};
+use crate::spec::{FigureSpec, Part, Shape};
+use serde_derive::Serialize;
+
+// `distance` and `compared_to` are the two values the shape's function
+// compares; they are absent for polygons, which have no such pair.
+#[derive(Serialize)]
+pub struct Test {
+    pub shape: Shape,
+    pub function: &'static str,
+    pub border: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<i128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compared_to: Option<i128>,
+    pub relation: Relation,
+}
+
+impl Test {
+    fn run(shape: &Shape, x: i32, y: i32, border: i32) -> Self {
+        let (x, y, b) = (i128::from(x), i128::from(y), i128::from(border));
+        let distance = shape.distance(x, y, b);
+        Test {
+            shape: shape.clone(),
+            function: shape.function(),
+            border,
+            distance: distance.map(|(distance, _)| distance),
+            compared_to: distance.map(|(_, compared_to)| compared_to),
+            relation: match distance {
+                Some((distance, compared_to)) => distance_relation(distance, compared_to),
+                None => shape.relation(x, y, b),
+            },
+        }
+    }
+}
+
+// `arm` names the branch of the ring walk that produced `relation`; `outer`
+// is absent when the inner test already decided the result, and `extra` holds
+// the tests against the figure's `extra_borders` that were needed. A point
+// that `axes = "border"` puts on the border has the arm `axis_border` and no
+// tests at all.
+#[derive(Serialize)]
+pub struct Explanation {
+    pub figure: String,
+    pub x: i32,
+    pub y: i32,
+    #[serde(flatten)]
+    pub part: Option<Part>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inner: Option<Test>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outer: Option<Test>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra: Vec<Test>,
+    pub arm: &'static str,
+    #[serde(flatten)]
+    pub ring: Option<Ring>,
+    pub relation: Relation,
+}
+
+pub fn explain(spec: &FigureSpec, x: i32, y: i32) -> Explanation {
+    let point = (i128::from(x), i128::from(y));
+    let Some((part, band, _)) = spec.resolve(point.0, point.1, |band| {
+        spec.band_ring(band, point.0, point.1)
+    }) else {
+        return Explanation {
+            figure: spec.name.clone(),
+            x,
+            y,
+            part: None,
+            inner: None,
+            outer: None,
+            extra: Vec::new(),
+            arm: "axis_border",
+            ring: None,
+            relation: Relation::Border,
+        };
+    };
+    let mut tests = Vec::new();
+    let ring = ring(spec.border_count(), |k| {
+        let test = Test::run(band.shape(k), x, y, spec.border(k));
+        let relation = test.relation;
+        tests.push(test);
+        relation
+    });
+    let last = tests.len() - 1;
+    let arm = match (last.min(2), tests[last].relation) {
+        (0, Relation::Border) => "inner_border",
+        (0, Relation::Inside) => "inner_inside",
+        (1, Relation::Border) => "outer_border",
+        (1, Relation::Inside) => "outer_inside",
+        (1, Relation::Outside) => "outer_outside",
+        (_, Relation::Border) => "extra_border",
+        (_, Relation::Inside) => "extra_inside",
+        (_, Relation::Outside) => "extra_outside",
+    };
+    let mut tests = tests.into_iter();
+    Explanation {
+        figure: spec.name.clone(),
+        x,
+        y,
+        part: Some(part),
+        inner: tests.next(),
+        outer: tests.next(),
+        extra: tests.collect(),
+        arm,
+        ring: Some(ring),
+        relation: ring.relation(),
+    }
+}
@@ -0,0 +1,530 @@
+use crate::csg::{complement, intersection, union};
+use crate::figure::{Exact, Range, Relation, MAX_MAGNITUDE};
+use serde_derive::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+// Figures written as inequalities, e.g.
+// `x > 0 ? max(|x|, |y|) > 10 && x^2 + y^2 < 400 : x^2 + y^2 < 100`.
+//
+// As a region, `a < b` (or `a <= b`) is inside where it holds strictly, on the
+// border where `a == b` and outside elsewhere; `&&`, `||` and `!` combine
+// regions like `csg::intersection`, `union` and `complement`. The condition of
+// `?:` is read as plain true or false, with `<=` and `>=` holding on equality,
+// as in the hand-written `point_location` functions. Numbers are integers
+// built from `x`, `y`, literals, `+`, `-`, `*`, `^` with a literal exponent,
+// `|a|`, `abs`, `min` and `max`.
+const MAX_EXPONENT: u32 = 16;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Token {
+    Number(i128),
+    Name(&'static str),
+    Symbol(&'static str),
+    End,
+}
+
+const NAMES: [&str; 5] = ["x", "y", "abs", "min", "max"];
+
+// Longest first, so `||` and `<=` win over `|` and `<`.
+const SYMBOLS: [&str; 17] = [
+    "||", "&&", "<=", ">=", "(", ")", ",", "|", "!", "?", ":", "<", ">", "+", "-", "*", "^",
+];
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let at = text.len() - rest.len();
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let n = rest[..end]
+                .parse()
+                .map_err(|_| format!("number too large at {}", at + 1))?;
+            tokens.push((Token::Number(n), at));
+            rest = &rest[end..];
+        } else if c.is_ascii_alphabetic() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            let name = NAMES
+                .iter()
+                .find(|&&name| name == &rest[..end])
+                .ok_or_else(|| format!("unknown name `{}` at {}", &rest[..end], at + 1))?;
+            tokens.push((Token::Name(name), at));
+            rest = &rest[end..];
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|&&symbol| rest.starts_with(symbol))
+                .ok_or_else(|| format!("unexpected `{}` at {}", c, at + 1))?;
+            tokens.push((Token::Symbol(symbol), at));
+            rest = &rest[symbol.len()..];
+        }
+    }
+    tokens.push((Token::End, text.len()));
+    Ok(tokens)
+}
+
+#[derive(Clone, Copy)]
+enum Compare {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+#[derive(Clone)]
+enum Node {
+    Number(i128),
+    X,
+    Y,
+    Neg(Box<Node>),
+    Add(Box<Node>, Box<Node>),
+    Sub(Box<Node>, Box<Node>),
+    Mul(Box<Node>, Box<Node>),
+    Pow(Box<Node>, u32),
+    Abs(Box<Node>),
+    Min(Vec<Node>),
+    Max(Vec<Node>),
+    Compare(Box<Node>, Compare, Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    Choose(Box<Node>, Box<Node>, Box<Node>),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Number,
+    Region,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Number => "a number",
+            Kind::Region => "a condition",
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Token {
+        self.tokens[self.next].0
+    }
+
+    fn at(&self) -> usize {
+        self.tokens[self.next].1 + 1
+    }
+
+    fn advance(&mut self) {
+        if self.peek() != Token::End {
+            self.next += 1;
+        }
+    }
+
+    fn eat(&mut self, symbol: &'static str) -> bool {
+        let found = self.peek() == Token::Symbol(symbol);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &'static str) -> Result<(), String> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(format!("expected `{}` at {}", symbol, self.at()))
+        }
+    }
+
+    fn unexpected(&self) -> String {
+        match self.peek() {
+            Token::End => "unexpected end of expression".to_string(),
+            Token::Number(n) => format!("unexpected `{}` at {}", n, self.at()),
+            Token::Name(name) | Token::Symbol(name) => {
+                format!("unexpected `{}` at {}", name, self.at())
+            }
+        }
+    }
+
+    // Parses one operand and checks that it is of `kind`.
+    fn operand(
+        &mut self,
+        kind: Kind,
+        parse: fn(&mut Self) -> Result<(Node, Kind), String>,
+    ) -> Result<Node, String> {
+        let at = self.at();
+        let (node, found) = parse(self)?;
+        if found != kind {
+            return Err(format!(
+                "expected {} at {}, found {}",
+                kind.name(),
+                at,
+                found.name()
+            ));
+        }
+        Ok(node)
+    }
+
+    fn choose(&mut self) -> Result<(Node, Kind), String> {
+        let (condition, kind) = self.or()?;
+        if !self.eat("?") {
+            return Ok((condition, kind));
+        }
+        if kind != Kind::Region {
+            return Err(format!("`?` needs a condition before it, at {}", self.at()));
+        }
+        let (then, kind) = self.choose()?;
+        self.expect(":")?;
+        let otherwise = self.operand(kind, Self::choose)?;
+        Ok((
+            Node::Choose(Box::new(condition), Box::new(then), Box::new(otherwise)),
+            kind,
+        ))
+    }
+
+    fn or(&mut self) -> Result<(Node, Kind), String> {
+        let at = self.at();
+        let (mut node, kind) = self.and()?;
+        if self.peek() != Token::Symbol("||") {
+            return Ok((node, kind));
+        }
+        if kind != Kind::Region {
+            return Err(format!("expected a condition at {}, found a number", at));
+        }
+        while self.eat("||") {
+            let right = self.operand(Kind::Region, Self::and)?;
+            node = Node::Or(Box::new(node), Box::new(right));
+        }
+        Ok((node, Kind::Region))
+    }
+
+    fn and(&mut self) -> Result<(Node, Kind), String> {
+        let at = self.at();
+        let (mut node, kind) = self.compare()?;
+        if self.peek() != Token::Symbol("&&") {
+            return Ok((node, kind));
+        }
+        if kind != Kind::Region {
+            return Err(format!("expected a condition at {}, found a number", at));
+        }
+        while self.eat("&&") {
+            let right = self.operand(Kind::Region, Self::compare)?;
+            node = Node::And(Box::new(node), Box::new(right));
+        }
+        Ok((node, Kind::Region))
+    }
+
+    fn compare(&mut self) -> Result<(Node, Kind), String> {
+        let at = self.at();
+        let (left, kind) = self.sum()?;
+        let op = match self.peek() {
+            Token::Symbol("<") => Compare::Less,
+            Token::Symbol("<=") => Compare::LessEqual,
+            Token::Symbol(">") => Compare::Greater,
+            Token::Symbol(">=") => Compare::GreaterEqual,
+            _ => return Ok((left, kind)),
+        };
+        if kind != Kind::Number {
+            return Err(format!("expected a number at {}, found a condition", at));
+        }
+        self.advance();
+        let right = self.operand(Kind::Number, Self::sum)?;
+        if matches!(self.peek(), Token::Symbol("<" | "<=" | ">" | ">=")) {
+            return Err(format!("comparisons do not chain, at {}", self.at()));
+        }
+        Ok((
+            Node::Compare(Box::new(left), op, Box::new(right)),
+            Kind::Region,
+        ))
+    }
+
+    fn sum(&mut self) -> Result<(Node, Kind), String> {
+        let at = self.at();
+        let (mut node, kind) = self.product()?;
+        while let Token::Symbol(op @ ("+" | "-")) = self.peek() {
+            if kind != Kind::Number {
+                return Err(format!("expected a number at {}, found a condition", at));
+            }
+            self.advance();
+            let right = self.operand(Kind::Number, Self::product)?;
+            node = match op {
+                "+" => Node::Add(Box::new(node), Box::new(right)),
+                _ => Node::Sub(Box::new(node), Box::new(right)),
+            };
+        }
+        Ok((node, kind))
+    }
+
+    fn product(&mut self) -> Result<(Node, Kind), String> {
+        let at = self.at();
+        let (mut node, kind) = self.unary()?;
+        while self.peek() == Token::Symbol("*") {
+            if kind != Kind::Number {
+                return Err(format!("expected a number at {}, found a condition", at));
+            }
+            self.advance();
+            let right = self.operand(Kind::Number, Self::unary)?;
+            node = Node::Mul(Box::new(node), Box::new(right));
+        }
+        Ok((node, kind))
+    }
+
+    fn unary(&mut self) -> Result<(Node, Kind), String> {
+        if self.eat("-") {
+            let node = self.operand(Kind::Number, Self::unary)?;
+            return Ok((Node::Neg(Box::new(node)), Kind::Number));
+        }
+        if self.eat("!") {
+            let node = self.operand(Kind::Region, Self::unary)?;
+            return Ok((Node::Not(Box::new(node)), Kind::Region));
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<(Node, Kind), String> {
+        let at = self.at();
+        let (node, kind) = self.atom()?;
+        if !self.eat("^") {
+            return Ok((node, kind));
+        }
+        if kind != Kind::Number {
+            return Err(format!("expected a number at {}, found a condition", at));
+        }
+        match self.peek() {
+            Token::Number(n) if n <= i128::from(MAX_EXPONENT) => {
+                self.advance();
+                Ok((Node::Pow(Box::new(node), n as u32), Kind::Number))
+            }
+            Token::Number(_) => Err(format!("exponents must be at most {}", MAX_EXPONENT)),
+            _ => Err(format!("`^` needs a literal exponent, at {}", self.at())),
+        }
+    }
+
+    fn atom(&mut self) -> Result<(Node, Kind), String> {
+        match self.peek() {
+            Token::Number(n) => {
+                self.advance();
+                Ok((Node::Number(n), Kind::Number))
+            }
+            Token::Name("x") => {
+                self.advance();
+                Ok((Node::X, Kind::Number))
+            }
+            Token::Name("y") => {
+                self.advance();
+                Ok((Node::Y, Kind::Number))
+            }
+            Token::Name(function) => {
+                self.advance();
+                self.expect("(")?;
+                let mut args = vec![self.operand(Kind::Number, Self::choose)?];
+                while self.eat(",") {
+                    args.push(self.operand(Kind::Number, Self::choose)?);
+                }
+                self.expect(")")?;
+                let node = match (function, args.len()) {
+                    ("abs", 1) => Node::Abs(Box::new(args.remove(0))),
+                    ("min", _) => Node::Min(args),
+                    ("max", _) => Node::Max(args),
+                    _ => return Err(format!("`{}` takes one argument", function)),
+                };
+                Ok((node, Kind::Number))
+            }
+            Token::Symbol("|") => {
+                self.advance();
+                let node = self.operand(Kind::Number, Self::choose)?;
+                self.expect("|")?;
+                Ok((Node::Abs(Box::new(node)), Kind::Number))
+            }
+            Token::Symbol("(") => {
+                self.advance();
+                let inner = self.choose()?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+}
+
+impl Node {
+    fn value<T: Exact>(&self, x: T, y: T) -> T {
+        let zero = T::from(0);
+        match self {
+            Node::Number(n) => T::from(*n),
+            Node::X => x,
+            Node::Y => y,
+            Node::Neg(a) => -a.value(x, y),
+            Node::Add(a, b) => a.value(x, y) + b.value(x, y),
+            Node::Sub(a, b) => a.value(x, y) + -b.value(x, y),
+            Node::Mul(a, b) => a.value(x, y) * b.value(x, y),
+            Node::Pow(a, k) => {
+                let a = a.value(x, y);
+                (0..*k).fold(T::from(1), |acc, _| acc * a)
+            }
+            Node::Abs(a) => {
+                let a = a.value(x, y);
+                a.max(-a)
+            }
+            Node::Min(args) => args.iter().map(|a| a.value(x, y)).min().unwrap_or(zero),
+            Node::Max(args) => args.iter().map(|a| a.value(x, y)).max().unwrap_or(zero),
+            Node::Choose(condition, then, otherwise) => {
+                if condition.holds(x, y) {
+                    then.value(x, y)
+                } else {
+                    otherwise.value(x, y)
+                }
+            }
+            Node::Compare(..) | Node::And(..) | Node::Or(..) | Node::Not(_) => {
+                unreachable!("conditions are checked when parsing")
+            }
+        }
+    }
+
+    fn order<T: Exact>(a: &Node, op: Compare, b: &Node, x: T, y: T) -> Ordering {
+        let ordering = a.value(x, y).cmp(&b.value(x, y));
+        match op {
+            Compare::Less | Compare::LessEqual => ordering,
+            Compare::Greater | Compare::GreaterEqual => ordering.reverse(),
+        }
+    }
+
+    fn holds<T: Exact>(&self, x: T, y: T) -> bool {
+        match self {
+            Node::Compare(a, op, b) => matches!(
+                (op, Node::order(a, *op, b, x, y)),
+                (_, Ordering::Less) | (Compare::LessEqual | Compare::GreaterEqual, Ordering::Equal)
+            ),
+            Node::And(a, b) => a.holds(x, y) && b.holds(x, y),
+            Node::Or(a, b) => a.holds(x, y) || b.holds(x, y),
+            Node::Not(a) => !a.holds(x, y),
+            Node::Choose(condition, then, otherwise) => {
+                if condition.holds(x, y) {
+                    then.holds(x, y)
+                } else {
+                    otherwise.holds(x, y)
+                }
+            }
+            _ => unreachable!("numbers are checked when parsing"),
+        }
+    }
+
+    fn relation<T: Exact>(&self, x: T, y: T) -> Relation {
+        match self {
+            Node::Compare(a, op, b) => match Node::order(a, *op, b, x, y) {
+                Ordering::Less => Relation::Inside,
+                Ordering::Equal => Relation::Border,
+                Ordering::Greater => Relation::Outside,
+            },
+            Node::And(a, b) => intersection(a.relation(x, y), b.relation(x, y)),
+            Node::Or(a, b) => union(a.relation(x, y), b.relation(x, y)),
+            Node::Not(a) => complement(a.relation(x, y)),
+            Node::Choose(condition, then, otherwise) => {
+                if condition.holds(x, y) {
+                    then.relation(x, y)
+                } else {
+                    otherwise.relation(x, y)
+                }
+            }
+            _ => unreachable!("numbers are checked when parsing"),
+        }
+    }
+
+    // An upper bound on the absolute value of every number the node forms for
+    // coordinates up to `reach`; `None` if that overflows.
+    fn magnitude(&self, reach: i128) -> Option<i128> {
+        let both = |a: &Node, b: &Node| Some((a.magnitude(reach)?, b.magnitude(reach)?));
+        match self {
+            Node::Number(n) => Some(n.abs()),
+            Node::X | Node::Y => Some(reach),
+            Node::Neg(a) | Node::Abs(a) | Node::Not(a) => a.magnitude(reach),
+            Node::Add(a, b) | Node::Sub(a, b) => {
+                let (a, b) = both(a, b)?;
+                a.checked_add(b)
+            }
+            Node::Mul(a, b) => {
+                let (a, b) = both(a, b)?;
+                a.checked_mul(b)
+            }
+            Node::Pow(a, k) => a.magnitude(reach)?.checked_pow(*k),
+            Node::Min(args) | Node::Max(args) => args
+                .iter()
+                .try_fold(0, |m, a| Some(a.magnitude(reach)?.max(m))),
+            Node::Compare(a, _, b) | Node::And(a, b) | Node::Or(a, b) => {
+                let (a, b) = both(a, b)?;
+                Some(a.max(b))
+            }
+            Node::Choose(condition, then, otherwise) => Some(
+                condition
+                    .magnitude(reach)?
+                    .max(then.magnitude(reach)?)
+                    .max(otherwise.magnitude(reach)?),
+            ),
+        }
+    }
+}
+
+// A parsed expression together with the text it came from, which is what
+// figure files and `/figures` show.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(try_from = "String", into = "String")]
+pub struct Expr {
+    text: String,
+    root: Node,
+}
+
+impl TryFrom<String> for Expr {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(&text)?,
+            next: 0,
+        };
+        let root = parser.operand(Kind::Region, Parser::choose)?;
+        if parser.peek() != Token::End {
+            return Err(parser.unexpected());
+        }
+        Ok(Expr { text, root })
+    }
+}
+
+impl From<Expr> for String {
+    fn from(expr: Expr) -> Self {
+        expr.text
+    }
+}
+
+impl Expr {
+    pub fn relation<T: Exact>(&self, x: T, y: T) -> Relation {
+        self.root.relation(x, y)
+    }
+
+    pub fn fits(&self, reach: i128) -> bool {
+        self.root
+            .magnitude(reach)
+            .is_some_and(|m| m <= MAX_MAGNITUDE)
+    }
+}
+
+// A figure given by an expression rather than by quadrant bands.
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Formula {
+    pub name: String,
+    #[serde(default)]
+    pub range: Range,
+    pub expression: Expr,
+}
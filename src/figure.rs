@@ -1,8 +1,10 @@
-use serde::{Deserialize, Deserializer};
-use serde_derive::Deserialize;
+use serde_derive::{Deserialize, Serialize};
 use std::fmt;
+use std::ops::{Add, Mul, Neg};
 
-#[cfg_attr(test, derive(PartialEq, Debug))]
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(test, derive(Debug))]
 pub enum Relation {
     Inside,
     Border,
@@ -19,30 +21,76 @@ impl fmt::Display for Relation {
     }
 }
 
+impl Relation {
+    pub fn symbol(&self) -> char {
+        match self {
+            Inside => 'i',
+            Border => 'b',
+            Outside => 'o',
+        }
+    }
+}
+
 use Relation::*;
 
-#[derive(Deserialize)]
+pub const COORD_MIN: i32 = -99;
+pub const COORD_MAX: i32 = 99;
+
+// A missing `min` or `max` leaves that side of the axis unbounded.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
 #[serde(deny_unknown_fields)]
-pub struct MyPoint {
-    #[serde(deserialize_with = "deserialize_coord")]
-    pub x: i32,
-    #[serde(deserialize_with = "deserialize_coord")]
-    pub y: i32,
+pub struct Bounds {
+    #[serde(default)]
+    pub min: Option<i32>,
+    #[serde(default)]
+    pub max: Option<i32>,
 }
 
-pub fn deserialize_coord<'de, D>(deserializer: D) -> Result<i32, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let c = i32::deserialize(deserializer)?;
-    if c.abs() >= 100 {
-        Err(serde::de::Error::custom("ERROR: out of range"))
-    } else {
-        Ok(c)
+impl Default for Bounds {
+    fn default() -> Self {
+        Bounds {
+            min: Some(COORD_MIN),
+            max: Some(COORD_MAX),
+        }
     }
 }
 
-fn distance_relation(distance: i32, border: i32) -> Relation {
+impl Bounds {
+    pub fn new(min: i32, max: i32) -> Self {
+        Bounds {
+            min: Some(min),
+            max: Some(max),
+        }
+    }
+
+    pub fn lo(&self) -> i32 {
+        self.min.unwrap_or(i32::MIN)
+    }
+
+    pub fn hi(&self) -> i32 {
+        self.max.unwrap_or(i32::MAX)
+    }
+
+    pub fn contains(&self, c: i128) -> bool {
+        i128::from(self.lo()) <= c && c <= i128::from(self.hi())
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Range {
+    #[serde(default)]
+    pub x: Bounds,
+    #[serde(default)]
+    pub y: Bounds,
+}
+
+pub struct MyPoint {
+    pub x: i32,
+    pub y: i32,
+}
+
+pub fn distance_relation<T: Ord>(distance: T, border: T) -> Relation {
     use std::cmp::Ordering::*;
 
     match distance.cmp(&border) {
@@ -52,38 +100,205 @@ fn distance_relation(distance: i32, border: i32) -> Relation {
     }
 }
 
-fn box_calc(x: i32, y: i32, border: i32) -> Relation {
-    let x = x.abs();
-    let y = y.abs();
+// The exact geometry below is written once for any ordered ring: `i128` for
+// lattice and scaled points, `Ratio` for fractions.
+pub trait Exact: Copy + Ord + From<i32> + From<i128>
+where
+    Self: Add<Output = Self> + Mul<Output = Self> + Neg<Output = Self>,
+{
+}
+
+impl<T> Exact for T
+where
+    T: Copy + Ord + From<i32> + From<i128>,
+    T: Add<Output = T> + Mul<Output = T> + Neg<Output = T>,
+{
+}
+
+// The `*_distance` functions return the two values `distance_relation`
+// compares, so callers can report them. They are exact as long as no value
+// formed on the way exceeds `MAX_MAGNITUDE`; `Shape::magnitude` bounds that
+// for given coordinates.
+pub const MAX_MAGNITUDE: i128 = 1 << 125;
+
+pub fn box_distance<T: Exact>(x: T, y: T, border: T) -> (T, T) {
+    let x = abs(x);
+    let y = abs(y);
     let dist = if y > x { y } else { x };
 
+    (dist, border)
+}
+
+pub fn radii_distance<T: Exact>(x: T, y: T, border: T) -> (T, T) {
+    (x * x + y * y, border * border)
+}
+
+pub fn box_calc<T: Exact>(x: T, y: T, border: T) -> Relation {
+    let (dist, border) = box_distance(x, y, border);
     distance_relation(dist, border)
 }
 
-fn radii_calc(x: i32, y: i32, border: i32) -> Relation {
-    distance_relation(x * x + y * y, border * border)
+pub fn radii_calc<T: Exact>(x: T, y: T, border: T) -> Relation {
+    let (dist, border) = radii_distance(x, y, border);
+    distance_relation(dist, border)
 }
 
-fn partition(
-    lo: impl Fn(i32, i32, i32) -> Relation,
-    h1: impl Fn(i32, i32, i32) -> Relation,
-    x: i32,
-    y: i32,
-    border_inner: i32,
-    border_outer: i32,
-) -> Relation {
-    match lo(x, y, border_inner) {
-        Border => Border,
-        Inside => Outside,
-        Outside => match h1(x, y, border_outer) {
-            Border => Border,
-            Inside => Inside,
-            Outside => Outside,
-        },
+fn abs<T: Exact>(x: T) -> T {
+    x.max(-x)
+}
+
+fn pow<T: Exact>(x: T, p: u32) -> T {
+    (1..p).fold(x, |acc, _| acc * x)
+}
+
+// Manhattan distance, a diamond with its corners on the axes.
+pub fn diamond_distance<T: Exact>(x: T, y: T, border: T) -> (T, T) {
+    (abs(x) + abs(y), border)
+}
+
+// `|x|^p + |y|^p` against `border^p`: `p = 1` is the diamond, `p = 2` the
+// circle, and larger `p` approach the box.
+pub fn lp_distance<T: Exact>(x: T, y: T, border: T, p: u32) -> (T, T) {
+    (pow(abs(x), p) + pow(abs(y), p), pow(border, p))
+}
+
+// The circle stretched by `a` along x and `b` along y, i.e.
+// `(x / a)^2 + (y / b)^2` against `border^2` with the fractions cleared.
+pub fn ellipse_distance<T: Exact>(x: T, y: T, border: T, (a, b): (i32, i32)) -> (T, T) {
+    let (a, b) = (T::from(a), T::from(b));
+    (
+        b * b * x * x + a * a * y * y,
+        a * a * b * b * border * border,
+    )
+}
+
+// The box turned so one side points along `(c, s)`. Both coordinates of the
+// turned point are `sqrt(c^2 + s^2)` times too long, so everything is compared
+// squared.
+pub fn rotated_box_distance<T: Exact>(x: T, y: T, border: T, (c, s): (i32, i32)) -> (T, T) {
+    let (c, s) = (T::from(c), T::from(s));
+    let u = c * x + s * y;
+    let v = c * y + -(s * x);
+    ((u * u).max(v * v), border * border * (c * c + s * s))
+}
+
+pub fn diamond_calc<T: Exact>(x: T, y: T, border: T) -> Relation {
+    let (dist, border) = diamond_distance(x, y, border);
+    distance_relation(dist, border)
+}
+
+pub fn lp_calc<T: Exact>(x: T, y: T, border: T, p: u32) -> Relation {
+    let (dist, border) = lp_distance(x, y, border, p);
+    distance_relation(dist, border)
+}
+
+pub fn ellipse_calc<T: Exact>(x: T, y: T, border: T, axes: (i32, i32)) -> Relation {
+    let (dist, border) = ellipse_distance(x, y, border, axes);
+    distance_relation(dist, border)
+}
+
+pub fn rotated_box_calc<T: Exact>(x: T, y: T, border: T, side: (i32, i32)) -> Relation {
+    let (dist, border) = rotated_box_distance(x, y, border, side);
+    distance_relation(dist, border)
+}
+
+// Real-valued counterparts: a point within `eps` of the border counts as on
+// it, so `eps` is half the thickness of the border line.
+pub fn distance_relation_f64(distance: f64, border: f64, eps: f64) -> Relation {
+    if (distance - border).abs() <= eps {
+        Border
+    } else if distance < border {
+        Inside
+    } else {
+        Outside
     }
 }
 
+pub fn box_calc_f64(x: f64, y: f64, border: f64, eps: f64) -> Relation {
+    distance_relation_f64(x.abs().max(y.abs()), border, eps)
+}
+
+pub fn radii_calc_f64(x: f64, y: f64, border: f64, eps: f64) -> Relation {
+    distance_relation_f64(x.hypot(y), border, eps)
+}
+
+pub fn diamond_calc_f64(x: f64, y: f64, border: f64, eps: f64) -> Relation {
+    distance_relation_f64(x.abs() + y.abs(), border, eps)
+}
+
+pub fn lp_calc_f64(x: f64, y: f64, border: f64, p: u32, eps: f64) -> Relation {
+    let p = f64::from(p);
+    let norm = (x.abs().powf(p) + y.abs().powf(p)).powf(p.recip());
+    distance_relation_f64(norm, border, eps)
+}
+
+pub fn ellipse_calc_f64(x: f64, y: f64, border: f64, (a, b): (i32, i32), eps: f64) -> Relation {
+    distance_relation_f64((x / f64::from(a)).hypot(y / f64::from(b)), border, eps)
+}
+
+pub fn rotated_box_calc_f64(x: f64, y: f64, border: f64, (c, s): (i32, i32), eps: f64) -> Relation {
+    let (c, s) = (f64::from(c), f64::from(s));
+    let n = c.hypot(s);
+    let (u, v) = ((c * x + s * y) / n, (c * y - s * x) / n);
+    distance_relation_f64(u.abs().max(v.abs()), border, eps)
+}
+
+// Where a point lies among nested borders `0..n`: on border `k`, or in ring
+// `k`, inside border `k` but outside all the ones before it (ring `n` is
+// beyond the last). Rings alternate, starting outside: with two borders only
+// ring 1, between them, is inside.
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(test, derive(PartialEq, Debug))]
+pub enum Ring {
+    Border(usize),
+    Ring(usize),
+}
+
+impl Ring {
+    pub fn relation(self) -> Relation {
+        match self {
+            Ring::Border(_) => Border,
+            Ring::Ring(k) if k % 2 == 1 => Inside,
+            Ring::Ring(_) => Outside,
+        }
+    }
+}
+
+// `test(k)` classifies the point against border `k`; borders are tried from
+// the innermost out until one does not have the point outside.
+pub fn ring(borders: usize, mut test: impl FnMut(usize) -> Relation) -> Ring {
+    for k in 0..borders {
+        match test(k) {
+            Border => return Ring::Border(k),
+            Inside => return Ring::Ring(k),
+            Outside => {}
+        }
+    }
+    Ring::Ring(borders)
+}
+
+// Reference implementations that `figures.toml` has to reproduce, written
+// with the original two-border rule that `ring` generalizes.
+#[cfg(test)]
+pub fn partition<T: Copy>(
+    lo: impl Fn(T, T, T) -> Relation,
+    h1: impl Fn(T, T, T) -> Relation,
+    x: T,
+    y: T,
+    border_inner: T,
+    border_outer: T,
+) -> Relation {
+    ring(2, |k| match k {
+        0 => lo(x, y, border_inner),
+        _ => h1(x, y, border_outer),
+    })
+    .relation()
+}
+
+#[cfg(test)]
 pub fn point_location1(x: i32, y: i32) -> Relation {
+    let (x, y) = (i128::from(x), i128::from(y));
     let border_inner = 10;
     let border_outer = 20;
     #[allow(clippy::collapsible_else_if)]
@@ -102,10 +317,12 @@ pub fn point_location1(x: i32, y: i32) -> Relation {
     }
 }
 
+#[cfg(test)]
 pub fn point_location2(x: i32, y: i32) -> Relation {
+    let (x, y) = (i128::from(x), i128::from(y));
     let border_inner = 20;
     let border_outer = 40;
-    #[allow(clippy::collapsible_else_if)]
+    #[allow(clippy::collapsible_else_if, clippy::if_same_then_else)]
     if x > 0 {
         if y > 0 {
             partition(box_calc, radii_calc, x, y, border_inner, border_outer)
@@ -0,0 +1,75 @@
+use crate::figure::{Bounds, Relation};
+use crate::registry::Figure;
+
+pub const MAX_GRID_CELLS: u64 = 1 << 16;
+
+pub enum GridFormat {
+    Text,
+    Json,
+}
+
+// Rows run from `y1` down to `y0` so the text form reads like a picture;
+// bounds given in either order are normalised.
+pub struct Grid {
+    pub x: (i32, i32),
+    pub y: (i32, i32),
+    pub step: i32,
+}
+
+impl Grid {
+    pub fn new(x: (i32, i32), y: (i32, i32), step: i32) -> Self {
+        Grid {
+            x: (x.0.min(x.1), x.0.max(x.1)),
+            y: (y.0.min(y.1), y.0.max(y.1)),
+            step,
+        }
+    }
+
+    pub fn columns(&self) -> impl Iterator<Item = i32> {
+        (self.x.0..=self.x.1).step_by(self.step as usize)
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = i32> {
+        (self.y.0..=self.y.1).rev().step_by(self.step as usize)
+    }
+
+    // Saturates at `u64::MAX`: two full `i32` spans have 2^64 cells.
+    pub fn cells(&self) -> u64 {
+        let span =
+            |(lo, hi): (i32, i32)| (i64::from(hi) - i64::from(lo)) as u128 / self.step as u128 + 1;
+        u64::try_from(span(self.x) * span(self.y)).unwrap_or(u64::MAX)
+    }
+
+    pub fn classify(&self, figure: &dyn Figure) -> Vec<Vec<Relation>> {
+        self.lines()
+            .map(|y| self.columns().map(|x| figure.locate(x, y)).collect())
+            .collect()
+    }
+}
+
+pub fn to_text(rows: &[Vec<Relation>]) -> String {
+    let mut text = String::new();
+    for row in rows {
+        text.extend(row.iter().map(Relation::symbol));
+        text.push('\n');
+    }
+    text
+}
+
+// The first and last multiples of `step` within `bounds`, so that sampled
+// rows and columns line up with the axes; falls back to the bounds
+// themselves when no multiple fits.
+pub fn aligned(bounds: Bounds, step: i32) -> (i32, i32) {
+    let (lo, hi, step) = (
+        i64::from(bounds.lo()),
+        i64::from(bounds.hi()),
+        i64::from(step),
+    );
+    let first = -(-lo).div_euclid(step) * step;
+    let last = hi.div_euclid(step) * step;
+    if first > last {
+        (bounds.lo(), bounds.hi())
+    } else {
+        (first as i32, last as i32)
+    }
+}
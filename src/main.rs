@@ -1,29 +1,325 @@
-use warp::{http::Response, Filter};
+use registry::{Figure, Registry};
+use serde_derive::Serialize;
+use std::env;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+use warp::{Filter, Rejection, Reply};
 
+mod csg;
+mod distance;
+mod errors;
+mod explain;
+mod expr;
 mod figure;
+mod grid;
+mod negotiate;
+mod path;
+mod polygon;
+mod query;
+mod ratio;
+mod registry;
+mod render;
+mod sector;
+mod spec;
+mod stats;
 
-fn routes() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
-    let figure1 = warp::path("figure-1")
-        .and(warp::query::<figure::MyPoint>())
-        .map(|p: figure::MyPoint| {
-            let relation = figure::point_location1(p.x, p.y);
-            Response::builder().body(relation.to_string())
+const FIGURES: &str = "figures.toml";
+
+// `/figure/{name}` answers unknown names with a JSON 404, while the bare
+// `/{name}` aliases fall through to warp's plain not-found.
+fn with_figure(
+    registry: Arc<Registry>,
+    report_unknown: bool,
+) -> impl Filter<Extract = (Arc<dyn Figure>,), Error = Rejection> + Clone {
+    warp::path::param::<String>().and_then(move |name: String| {
+        let registry = registry.clone();
+        async move {
+            registry.get(&name).ok_or_else(|| {
+                if report_unknown {
+                    warp::reject::custom(errors::UnknownFigure::new(name, registry.names()))
+                } else {
+                    warp::reject::not_found()
+                }
+            })
+        }
+    })
+}
+
+// The spec behind endpoints that only figures with quadrants or sectors
+// answer.
+fn spec_of(figure: &dyn Figure) -> Result<&spec::FigureSpec, Rejection> {
+    figure
+        .spec()
+        .ok_or_else(|| warp::reject::custom(errors::Unsupported::new(figure.name())))
+}
+
+const BATCH_BODY_LIMIT: u64 = 4 * 1024 * 1024;
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum BatchItem {
+    Relation(figure::Relation),
+    Error(errors::FieldError),
+}
+
+fn figure_routes(
+    figure: impl Filter<Extract = (Arc<dyn Figure>,), Error = Rejection> + Clone + Send + Sync,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = Rejection> + Clone {
+    let classify = figure
+        .clone()
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(query::raw())
+        .and(negotiate::format())
+        .and_then(|figure: Arc<dyn Figure>, raw: String, format| async move {
+            let q = query::parse_point(&raw, &figure.range()).map_err(warp::reject::custom)?;
+            let p = q.point;
+            if !q.explain {
+                let classification = negotiate::Classification {
+                    x: p.x,
+                    y: p.y,
+                    relation: figure.locate(p.x, p.y),
+                    figure: figure.name(),
+                    ring: figure
+                        .spec()
+                        .and_then(|spec| spec.ring_exact(i128::from(p.x), i128::from(p.y))),
+                };
+                return Ok::<_, Rejection>(negotiate::reply(format, &classification));
+            }
+            let spec = spec_of(figure.as_ref())?;
+            Ok(Box::new(warp::reply::json(&explain::explain(
+                spec, p.x, p.y,
+            ))))
+        });
+
+    let real = figure
+        .clone()
+        .and(warp::path!("real"))
+        .and(warp::get())
+        .and(query::raw())
+        .and_then(|figure: Arc<dyn Figure>, raw: String| async move {
+            let spec = spec_of(figure.as_ref())?;
+            let q = query::parse_real(&raw, spec).map_err(warp::reject::custom)?;
+            let relation = match q {
+                query::RealQuery::Float { x, y, eps } => spec.locate_f64(x, y, eps),
+                query::RealQuery::Exact { x, y } => spec.locate_exact(x, y),
+            };
+            Ok::<_, Rejection>(relation.to_string())
+        });
+
+    let distance = figure
+        .clone()
+        .and(warp::path!("distance"))
+        .and(warp::get())
+        .and(query::raw())
+        .and_then(|figure: Arc<dyn Figure>, raw: String| async move {
+            let spec = spec_of(figure.as_ref())?;
+            let point = query::parse_xy(&raw, &spec.range).map_err(warp::reject::custom)?;
+            Ok::<_, Rejection>(warp::reply::json(&distance::distance(
+                spec, point.x, point.y,
+            )))
+        });
+
+    let nearest = figure
+        .clone()
+        .and(warp::path!("nearest"))
+        .and(warp::get())
+        .and(query::raw())
+        .and_then(|figure: Arc<dyn Figure>, raw: String| async move {
+            let spec = spec_of(figure.as_ref())?;
+            let point = query::parse_xy(&raw, &spec.range).map_err(warp::reject::custom)?;
+            Ok::<_, Rejection>(warp::reply::json(&distance::nearest(
+                spec, point.x, point.y,
+            )))
         });
 
-    let figure2 = warp::path("figure-2")
-        .and(warp::query::<figure::MyPoint>())
-        .map(|p: figure::MyPoint| {
-            //TODO: implement as new logic for figure-2
-            let relation = figure::point_location2(p.x, p.y);
-            Response::builder().body(relation.to_string())
+    let batch = figure
+        .clone()
+        .and(warp::path!("batch"))
+        .and(warp::post())
+        .and(warp::body::content_length_limit(BATCH_BODY_LIMIT))
+        .and(warp::body::json())
+        .map(|figure: Arc<dyn Figure>, points: Vec<serde_json::Value>| {
+            let range = figure.range();
+            let items: Vec<_> = points
+                .iter()
+                .map(|point| match query::point_from_json(point, &range) {
+                    Ok(p) => BatchItem::Relation(figure.locate(p.x, p.y)),
+                    Err(e) => BatchItem::Error(e),
+                })
+                .collect();
+            warp::reply::json(&items)
         });
 
-    warp::get().and(figure1.or(figure2))
+    let path = figure
+        .clone()
+        .and(warp::path!("path"))
+        .and(warp::post())
+        .and(warp::body::content_length_limit(BATCH_BODY_LIMIT))
+        .and(warp::body::json())
+        .and_then(
+            |figure: Arc<dyn Figure>, points: Vec<serde_json::Value>| async move {
+                let spec = spec_of(figure.as_ref())?;
+                let points =
+                    query::parse_path(&points, &spec.range).map_err(warp::reject::custom)?;
+                Ok::<_, Rejection>(warp::reply::json(&path::path(spec, &points)))
+            },
+        );
+
+    let grid = figure
+        .clone()
+        .and(warp::path!("grid"))
+        .and(warp::get())
+        .and(query::raw())
+        .and_then(|figure: Arc<dyn Figure>, raw: String| async move {
+            let (grid, format) =
+                query::parse_grid(&raw, &figure.range()).map_err(warp::reject::custom)?;
+            let rows = grid.classify(figure.as_ref());
+            Ok::<_, Rejection>(match format {
+                grid::GridFormat::Text => Box::new(grid::to_text(&rows)) as Box<dyn Reply>,
+                grid::GridFormat::Json => Box::new(warp::reply::json(&rows)),
+            })
+        });
+
+    let stats = figure
+        .clone()
+        .and(warp::path!("stats"))
+        .and(warp::get())
+        .and_then(|figure: Arc<dyn Figure>| async move {
+            let spec = spec_of(figure.as_ref())?;
+            Ok::<_, Rejection>(warp::reply::json(&stats::stats(spec)))
+        });
+
+    let svg = figure
+        .clone()
+        .and(warp::path!("render.svg"))
+        .and(warp::get())
+        .and_then(|figure: Arc<dyn Figure>| async move {
+            let spec = spec_of(figure.as_ref())?;
+            Ok::<_, Rejection>(warp::reply::with_header(
+                render::svg(spec),
+                "content-type",
+                "image/svg+xml",
+            ))
+        });
+
+    let png = figure
+        .clone()
+        .and(warp::path!("render.png"))
+        .and(warp::get())
+        .and(query::raw())
+        .and_then(|figure: Arc<dyn Figure>, raw: String| async move {
+            let (scale, palette) =
+                query::parse_png(&raw, &figure.range()).map_err(warp::reject::custom)?;
+            let image = render::png(figure.as_ref(), scale, &palette);
+            Ok::<_, Rejection>(warp::reply::with_header(image, "content-type", "image/png"))
+        });
+
+    let ascii = figure
+        .clone()
+        .and(warp::path!("ascii"))
+        .and(warp::get())
+        .and(query::raw())
+        .and_then(|figure: Arc<dyn Figure>, raw: String| async move {
+            let step = query::parse_ascii(&raw, &figure.range()).map_err(warp::reject::custom)?;
+            Ok::<_, Rejection>(render::ascii(figure.as_ref(), step))
+        });
+
+    classify
+        .or(real)
+        .or(distance)
+        .or(nearest)
+        .or(batch)
+        .or(path)
+        .or(grid)
+        .or(stats)
+        .or(svg)
+        .or(png)
+        .or(ascii)
+}
+
+fn routes(
+    registry: Arc<Registry>,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    let listing = {
+        let registry = registry.clone();
+        warp::path!("figures").map(move || {
+            let figures: Vec<_> = registry.figures().map(|f| f.metadata()).collect();
+            warp::reply::json(&figures)
+        })
+    };
+    let named = warp::path("figure").and(figure_routes(with_figure(registry.clone(), true)));
+    let aliases = figure_routes(with_figure(registry, false));
+
+    warp::get()
+        .and(listing)
+        .or(named)
+        .or(aliases)
+        .recover(errors::handle_rejection)
 }
 
 #[tokio::main]
 async fn main() {
-    warp::serve(routes()).run(([127, 0, 0, 1], 3030)).await;
+    let path = env::var("LAB8_FIGURES").unwrap_or_else(|_| FIGURES.to_string());
+    let figures = spec::load(Path::new(&path)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let registry = Arc::new(Registry::from(figures));
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => {
+            warp::serve(routes(registry))
+                .run(([127, 0, 0, 1], 3030))
+                .await
+        }
+        ["ascii", name] => match registry.get(name) {
+            Some(figure) => {
+                let step = render::ascii_step(&figure.range(), terminal_columns());
+                print!("{}", render::ascii(figure.as_ref(), step))
+            }
+            None => {
+                eprintln!(
+                    "unknown figure `{}`, known figures: {}",
+                    name,
+                    registry.names().join(", ")
+                );
+                process::exit(1);
+            }
+        },
+        _ => {
+            eprintln!("usage: lab8 [ascii <figure>]");
+            process::exit(2);
+        }
+    }
+}
+
+// The width of the terminal on stdout, then `COLUMNS` (which shells set but
+// do not usually export), then 80.
+fn terminal_columns() -> usize {
+    tty_columns()
+        .or_else(|| env::var("COLUMNS").ok().and_then(|c| c.parse().ok()))
+        .unwrap_or(render::ASCII_COLUMNS)
+}
+
+#[cfg(unix)]
+fn tty_columns() -> Option<usize> {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCGWINSZ only writes a `winsize` through the pointer.
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    (ok && size.ws_col > 0).then_some(usize::from(size.ws_col))
+}
+
+#[cfg(not(unix))]
+fn tty_columns() -> Option<usize> {
+    None
 }
 
 #[cfg(test)]
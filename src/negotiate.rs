@@ -0,0 +1,137 @@
+use crate::errors::NotAcceptable;
+use crate::figure::{Relation, Ring};
+use serde_derive::Serialize;
+use warp::{Filter, Rejection, Reply};
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Text,
+    Json,
+    Csv,
+    Cbor,
+    MsgPack,
+}
+
+const FORMATS: [(&str, Format); 6] = [
+    ("text/plain", Format::Text),
+    ("application/json", Format::Json),
+    ("text/csv", Format::Csv),
+    ("application/cbor", Format::Cbor),
+    ("application/msgpack", Format::MsgPack),
+    ("application/x-msgpack", Format::MsgPack),
+];
+
+impl Format {
+    fn content_type(self) -> &'static str {
+        match self {
+            Format::Text => "text/plain; charset=utf-8",
+            Format::Json => "application/json",
+            Format::Csv => "text/csv",
+            Format::Cbor => "application/cbor",
+            Format::MsgPack => "application/msgpack",
+        }
+    }
+}
+
+pub fn supported() -> Vec<&'static str> {
+    FORMATS.iter().map(|(media, _)| *media).collect()
+}
+
+fn covers(range: &str, media: &str) -> bool {
+    match range.split_once('/') {
+        Some(("*", "*")) => true,
+        Some((kind, "*")) => media.split('/').next() == Some(kind),
+        _ => range == media,
+    }
+}
+
+// Picks the supported type with the highest `q`; on ties the earlier entry of
+// the header wins, and `*/*` resolves to plain text.
+pub fn select(accept: Option<&str>) -> Option<Format> {
+    let accept = match accept {
+        Some(accept) if !accept.trim().is_empty() => accept,
+        _ => return Some(Format::Text),
+    };
+    let mut best: Option<(f32, Format)> = None;
+    for item in accept.split(',') {
+        let mut parts = item.split(';').map(str::trim);
+        let range = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let q = parts
+            .filter_map(|p| p.strip_prefix("q="))
+            .find_map(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if q <= 0.0 {
+            continue;
+        }
+        let format = FORMATS
+            .iter()
+            .find(|(media, _)| covers(&range, media))
+            .map(|(_, format)| *format);
+        if let Some(format) = format {
+            if best.is_none_or(|(best_q, _)| q > best_q) {
+                best = Some((q, format));
+            }
+        }
+    }
+    best.map(|(_, format)| format)
+}
+
+pub fn format() -> impl Filter<Extract = (Format,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("accept").and_then(|accept: Option<String>| async move {
+        select(accept.as_deref()).ok_or_else(|| warp::reject::custom(NotAcceptable::new()))
+    })
+}
+
+// `ring` is reported for figures with quadrants or sectors, as `explain`
+// reports it.
+#[derive(Serialize)]
+pub struct Classification<'a> {
+    pub x: i32,
+    pub y: i32,
+    pub relation: Relation,
+    pub figure: &'a str,
+    #[serde(flatten)]
+    pub ring: Option<Ring>,
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn reply(format: Format, c: &Classification) -> Box<dyn Reply> {
+    let body = match format {
+        Format::Text => c.relation.to_string().into_bytes(),
+        Format::Json => serde_json::to_vec(c).unwrap_or_default(),
+        Format::Csv => {
+            let (header, value) = match c.ring {
+                Some(Ring::Ring(k)) => (",ring", format!(",{}", k)),
+                Some(Ring::Border(k)) => (",border", format!(",{}", k)),
+                None => ("", String::new()),
+            };
+            format!(
+                "x,y,relation,figure{}\n{},{},{},{}{}\n",
+                header,
+                c.x,
+                c.y,
+                c.relation,
+                csv_field(c.figure),
+                value
+            )
+            .into_bytes()
+        }
+        Format::Cbor => {
+            let mut body = Vec::new();
+            ciborium::into_writer(c, &mut body).map_or_else(|_| Vec::new(), |_| body)
+        }
+        Format::MsgPack => rmp_serde::to_vec_named(c).unwrap_or_default(),
+    };
+    Box::new(warp::reply::with_header(
+        body,
+        "content-type",
+        format.content_type(),
+    ))
+}
@@ -0,0 +1,428 @@
+use crate::figure::{MyPoint, Relation};
+use crate::ratio::Ratio;
+use crate::spec::{FigureSpec, Shape};
+use serde_derive::Serialize;
+
+// Stops closer than this along a leg are taken for the same one, and float
+// stops this close to either end of the leg for the end itself.
+const SAME_STOP: f64 = 1e-12;
+// Half the thickness of the border when classifying a stop whose position is
+// only known as a float, per unit of the figure's largest border.
+const STOP_EPS: f64 = 1e-9;
+// Float parameters are rounded to this denominator to classify the piece
+// between two stops exactly.
+const DYADIC: i128 = 1 << 40;
+
+// Points of a path are exact, as integers or fractions such as `"15/2"`,
+// whenever they are rational; crossings of circles, ellipses, `lp` curves and
+// boxes turned by an irrational angle are floats.
+#[derive(Serialize, Clone, Copy)]
+#[serde(untagged)]
+pub enum PathPoint {
+    Exact([Ratio; 2]),
+    Float([f64; 2]),
+}
+
+// A straight piece of leg `leg` (from point `leg` to point `leg + 1` of the
+// path) with one relation all along, ends excluded.
+#[derive(Serialize)]
+pub struct Segment {
+    pub leg: usize,
+    pub from: PathPoint,
+    pub to: PathPoint,
+    pub relation: Relation,
+}
+
+// A point inside the path where its relation changes: `before` and `after`
+// are the relations of the pieces on either side and `relation` the one of
+// the point itself, so `outside` -> `inside` enters the figure and a border
+// point between two equal pieces only touches it. A crossing on a vertex
+// belongs to the leg leaving it.
+#[derive(Serialize)]
+pub struct Crossing {
+    pub leg: usize,
+    pub point: PathPoint,
+    pub relation: Relation,
+    pub before: Relation,
+    pub after: Relation,
+}
+
+#[derive(Serialize)]
+pub struct PathReport {
+    pub figure: String,
+    pub segments: Vec<Segment>,
+    pub crossings: Vec<Crossing>,
+}
+
+// Where along a leg a stop is: `t` for ordering, with the exact parameter and
+// point when they are rational and small enough to compare.
+#[derive(Clone, Copy)]
+struct Stop {
+    t: f64,
+    exact: Option<(Ratio, [Ratio; 2])>,
+}
+
+impl Stop {
+    fn same(&self, other: &Stop) -> bool {
+        match (self.exact, other.exact) {
+            (Some((s, _)), Some((t, _))) => s == t,
+            _ => (self.t - other.t).abs() < SAME_STOP,
+        }
+    }
+
+    fn inside_leg(&self) -> bool {
+        match self.exact {
+            Some((t, _)) => 0 < t.num() && t.num() < t.den(),
+            None => SAME_STOP < self.t && self.t < 1.0 - SAME_STOP,
+        }
+    }
+}
+
+// The points `a + t d` for `0 <= t <= 1`.
+struct Leg {
+    a: (i128, i128),
+    d: (i128, i128),
+}
+
+impl Leg {
+    fn new(from: &MyPoint, to: &MyPoint) -> Self {
+        let a = (i128::from(from.x), i128::from(from.y));
+        Leg {
+            a,
+            d: (i128::from(to.x) - a.0, i128::from(to.y) - a.1),
+        }
+    }
+
+    fn at_f64(&self, t: f64) -> (f64, f64) {
+        (
+            self.a.0 as f64 + t * self.d.0 as f64,
+            self.a.1 as f64 + t * self.d.1 as f64,
+        )
+    }
+
+    fn float(&self, t: f64) -> Stop {
+        Stop { t, exact: None }
+    }
+
+    // Falls back to a float stop when the point does not fit `i128`.
+    fn exact(&self, t: Ratio) -> Stop {
+        let at = |a: i128, d: i128| {
+            let num = a
+                .checked_mul(t.den())?
+                .checked_add(t.num().checked_mul(d)?)?;
+            Some(Ratio::new(num, t.den()))
+        };
+        let exact = match (at(self.a.0, self.d.0), at(self.a.1, self.d.1)) {
+            (Some(x), Some(y)) if i64::try_from(t.den()).is_ok() => Some((t, [x, y])),
+            _ => None,
+        };
+        Stop {
+            t: t.num() as f64 / t.den() as f64,
+            exact,
+        }
+    }
+
+    fn point(&self, stop: &Stop) -> PathPoint {
+        match stop.exact {
+            Some((_, point)) => PathPoint::Exact(point),
+            None => {
+                let (x, y) = self.at_f64(stop.t);
+                PathPoint::Float([x, y])
+            }
+        }
+    }
+
+    // A stop strictly between two others: their midpoint when both are exact,
+    // otherwise the float midpoint rounded to a multiple of `1 / DYADIC`.
+    fn between(&self, from: &Stop, to: &Stop) -> Stop {
+        if let (Some((s, _)), Some((t, _))) = (from.exact, to.exact) {
+            let mid = || {
+                let num = s.num().checked_mul(t.den())? + t.num().checked_mul(s.den())?;
+                Some(Ratio::new(
+                    num,
+                    s.den().checked_mul(t.den())?.checked_mul(2)?,
+                ))
+            };
+            if let Some(mid) = mid() {
+                return self.exact(mid);
+            }
+        }
+        let mid = (from.t + to.t) / 2.0;
+        self.exact(Ratio::new((mid * DYADIC as f64).round() as i128, DYADIC))
+    }
+
+    fn classify(&self, spec: &FigureSpec, stop: &Stop, eps: f64) -> Relation {
+        match stop.exact {
+            Some((_, [x, y])) if spec.ratio_fits(x, y) && spec.ratio_fits(y, x) => {
+                spec.locate_exact(x, y)
+            }
+            _ => {
+                let (x, y) = self.at_f64(stop.t);
+                spec.locate_f64(x, y, eps)
+            }
+        }
+    }
+
+    // Where the leg meets the line `alpha x + beta y = gamma`; nowhere when it
+    // runs parallel to it, along it included.
+    fn line(&self, (alpha, beta, gamma): (i128, i128, i128)) -> Option<Stop> {
+        let exact = || {
+            let den = alpha
+                .checked_mul(self.d.0)?
+                .checked_add(beta.checked_mul(self.d.1)?)?;
+            let num = gamma
+                .checked_sub(alpha.checked_mul(self.a.0)?)?
+                .checked_sub(beta.checked_mul(self.a.1)?)?;
+            Some((num, den))
+        };
+        match exact() {
+            Some((_, 0)) => None,
+            Some((num, den)) => Some(self.exact(Ratio::new(num, den))),
+            None => self.line_f64((alpha as f64, beta as f64), gamma as f64),
+        }
+    }
+
+    fn line_f64(&self, (alpha, beta): (f64, f64), gamma: f64) -> Option<Stop> {
+        let den = alpha * self.d.0 as f64 + beta * self.d.1 as f64;
+        let num = gamma - alpha * self.a.0 as f64 - beta * self.a.1 as f64;
+        (den != 0.0).then(|| self.float(num / den))
+    }
+
+    // Where the leg meets `p x^2 + r y^2 = s`: the roots of
+    // `a t^2 + 2 h t + c`, exact when the discriminant is a square.
+    fn conic(&self, p: i128, r: i128, s: i128) -> Vec<Stop> {
+        let ((ax, ay), (dx, dy)) = (self.a, self.d);
+        let exact = || {
+            let a = p
+                .checked_mul(dx * dx)?
+                .checked_add(r.checked_mul(dy * dy)?)?;
+            let h = p
+                .checked_mul(ax * dx)?
+                .checked_add(r.checked_mul(ay * dy)?)?;
+            let c = p
+                .checked_mul(ax * ax)?
+                .checked_add(r.checked_mul(ay * ay)?)?
+                .checked_sub(s)?;
+            let disc = h.checked_mul(h)?.checked_sub(a.checked_mul(c)?)?;
+            Some((a, h, c, disc))
+        };
+        match exact() {
+            Some((_, _, _, disc)) if disc < 0 => vec![],
+            Some((a, h, _, disc)) if disc.isqrt().pow(2) == disc => {
+                let root = disc.isqrt();
+                vec![
+                    self.exact(Ratio::new(-h - root, a)),
+                    self.exact(Ratio::new(-h + root, a)),
+                ]
+            }
+            Some((a, h, c, _)) => self.roots_f64(a as f64, h as f64, c as f64),
+            None => {
+                let [ax, ay, dx, dy] = [ax, ay, dx, dy].map(|c| c as f64);
+                let (p, r) = (p as f64, r as f64);
+                let a = p * dx * dx + r * dy * dy;
+                let h = p * ax * dx + r * ay * dy;
+                let c = p * ax * ax + r * ay * ay - s as f64;
+                self.roots_f64(a, h, c)
+            }
+        }
+    }
+
+    // Without cancellation: the root away from `-h` first, the other from the
+    // product of the roots.
+    fn roots_f64(&self, a: f64, h: f64, c: f64) -> Vec<Stop> {
+        let disc = h * h - a * c;
+        if disc < 0.0 {
+            return vec![];
+        }
+        let q = -(h + disc.sqrt().copysign(h));
+        if q == 0.0 {
+            return vec![self.float(0.0)];
+        }
+        vec![self.float(q / a), self.float(c / q)]
+    }
+
+    // Where a convex `norm` along the leg equals `border`: on either side of
+    // its minimum, found by ternary search, it is monotonic and bisected.
+    fn convex_f64(&self, norm: impl Fn(f64, f64) -> f64, border: f64) -> Vec<Stop> {
+        let f = |t: f64| {
+            let (x, y) = self.at_f64(t);
+            norm(x, y) - border
+        };
+        let (mut lo, mut hi) = (0.0, 1.0);
+        for _ in 0..100 {
+            let (m1, m2) = (lo + (hi - lo) / 3.0, hi - (hi - lo) / 3.0);
+            if f(m1) < f(m2) {
+                hi = m2;
+            } else {
+                lo = m1;
+            }
+        }
+        let least = (lo + hi) / 2.0;
+        if f(least) > 0.0 {
+            return vec![];
+        }
+        let bisect = |mut inside: f64, mut outside: f64| {
+            for _ in 0..100 {
+                let mid = (inside + outside) / 2.0;
+                if f(mid) > 0.0 {
+                    outside = mid;
+                } else {
+                    inside = mid;
+                }
+            }
+            self.float((inside + outside) / 2.0)
+        };
+        [0.0, 1.0]
+            .into_iter()
+            .filter(|&end| f(end) > 0.0)
+            .map(|end| bisect(least, end))
+            .collect()
+    }
+
+    fn crossings(&self, shape: &Shape, border: i128) -> Vec<Stop> {
+        let lines = |lines: &[(i128, i128, i128)]| -> Vec<Stop> {
+            lines.iter().filter_map(|&line| self.line(line)).collect()
+        };
+        let b = border;
+        match shape {
+            Shape::Box => lines(&[(1, 0, b), (1, 0, -b), (0, 1, b), (0, 1, -b)]),
+            Shape::Diamond | Shape::Lp(1) => {
+                lines(&[(1, 1, b), (1, -1, b), (-1, 1, b), (-1, -1, b)])
+            }
+            Shape::Circle | Shape::Lp(2) => self.conic(1, 1, b * b),
+            Shape::Lp(p) => {
+                let p = f64::from(*p);
+                let norm = |x: f64, y: f64| (x.abs().powf(p) + y.abs().powf(p)).powf(p.recip());
+                self.convex_f64(norm, b as f64)
+            }
+            Shape::Ellipse([a, e]) => {
+                let (a, e) = (i128::from(*a), i128::from(*e));
+                self.conic(e * e, a * a, a * a * e * e * b * b)
+            }
+            Shape::RotatedBox([c, s]) => {
+                let (c, s) = (i128::from(*c), i128::from(*s));
+                let n2 = c * c + s * s;
+                let sides = [(c, s), (-s, c)];
+                if n2.isqrt().pow(2) == n2 {
+                    let n = n2.isqrt();
+                    let mut stops = vec![];
+                    for (alpha, beta) in sides {
+                        stops.extend(lines(&[(alpha, beta, b * n), (alpha, beta, -b * n)]));
+                    }
+                    return stops;
+                }
+                let reach = b as f64 * (n2 as f64).sqrt();
+                sides
+                    .into_iter()
+                    .flat_map(|(alpha, beta)| {
+                        let normal = (alpha as f64, beta as f64);
+                        [self.line_f64(normal, reach), self.line_f64(normal, -reach)]
+                    })
+                    .flatten()
+                    .collect()
+            }
+            // each edge, scaled by the polygon's `scale` to keep it integral
+            Shape::Polygon(polygon) => {
+                let (scale, lattice) = (polygon.scale(), polygon.lattice());
+                let edges = (0..lattice.len()).map(|i| {
+                    let ((x1, y1), (x2, y2)) = (lattice[i], lattice[(i + 1) % lattice.len()]);
+                    let (alpha, beta) = (y2 - y1, x1 - x2);
+                    (scale * alpha, scale * beta, b * (alpha * x1 + beta * y1))
+                });
+                lines(&edges.collect::<Vec<_>>())
+            }
+        }
+    }
+
+    // Every point of the leg where its relation may change, in order and
+    // including both ends: where it crosses a border of any band, an axis of
+    // a quadrant figure or the line of a sector ray.
+    fn stops(&self, spec: &FigureSpec) -> Vec<Stop> {
+        let mut stops: Vec<Stop> = match &spec.quadrants {
+            Some(_) => [(1, 0, 0), (0, 1, 0)]
+                .into_iter()
+                .filter_map(|line| self.line(line))
+                .collect(),
+            None => spec
+                .sectors
+                .iter()
+                .filter_map(|sector| {
+                    let (x, y) = sector.from.direction();
+                    self.line((y.into(), -i128::from(x), 0))
+                })
+                .collect(),
+        };
+        for band in spec.bands() {
+            for k in 0..spec.border_count() {
+                stops.extend(self.crossings(band.shape(k), spec.border(k).into()));
+            }
+        }
+        stops.retain(Stop::inside_leg);
+        stops.sort_by(|s, t| match (s.exact, t.exact) {
+            (Some((s, _)), Some((t, _))) => s.cmp(&t),
+            _ => s.t.total_cmp(&t.t),
+        });
+        let mut kept = vec![self.exact(Ratio::from(0))];
+        for stop in stops {
+            match kept.last_mut() {
+                Some(last) if last.same(&stop) => {
+                    if last.exact.is_none() {
+                        *last = stop;
+                    }
+                }
+                _ => kept.push(stop),
+            }
+        }
+        kept.push(self.exact(Ratio::from(1)));
+        kept
+    }
+}
+
+// Splits the polyline through `points` into pieces of one relation each, and
+// reports where the relation changes. Legs of zero length are skipped.
+pub fn path(spec: &FigureSpec, points: &[MyPoint]) -> PathReport {
+    let mut segments: Vec<Segment> = vec![];
+    let mut crossings = vec![];
+    let mut previous = None;
+    let eps = STOP_EPS * f64::from(spec.max_border().max(1));
+    for (l, pair) in points.windows(2).enumerate() {
+        let leg = Leg::new(&pair[0], &pair[1]);
+        if leg.d == (0, 0) {
+            continue;
+        }
+        let stops = leg.stops(spec);
+        for (i, pair) in stops.windows(2).enumerate() {
+            let (from, to) = (&pair[0], &pair[1]);
+            let start = leg.classify(spec, from, eps);
+            let relation = leg.classify(spec, &leg.between(from, to), 0.0);
+            if let Some(before) = previous {
+                if before != relation || start != relation {
+                    crossings.push(Crossing {
+                        leg: l,
+                        point: leg.point(from),
+                        relation: start,
+                        before,
+                        after: relation,
+                    });
+                }
+            }
+            match segments.last_mut() {
+                Some(last) if i > 0 && last.relation == relation && start == relation => {
+                    last.to = leg.point(to);
+                }
+                _ => segments.push(Segment {
+                    leg: l,
+                    from: leg.point(from),
+                    to: leg.point(to),
+                    relation,
+                }),
+            }
+            previous = Some(relation);
+        }
+    }
+    PathReport {
+        figure: spec.name.clone(),
+        segments,
+        crossings,
+    }
+}
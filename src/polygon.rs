@@ -0,0 +1,143 @@
+use crate::figure::{Exact, Relation};
+use crate::ratio::{gcd, Ratio};
+use serde_derive::{Deserialize, Serialize};
+
+// Even-odd point-in-polygon test that reports points on an edge or a vertex
+// as `Border`. Vertices are taken in order and the last one joins the first.
+pub fn polygon_calc<T: Exact>(x: T, y: T, vertices: &[(T, T)]) -> Relation {
+    let zero = T::from(0);
+    let mut inside = false;
+    for (i, &(ax, ay)) in vertices.iter().enumerate() {
+        let (bx, by) = vertices[(i + 1) % vertices.len()];
+        // > 0 when the point is left of the edge a -> b
+        let cross = (bx + -ax) * (y + -ay) + -((by + -ay) * (x + -ax));
+        if cross == zero && ax.min(bx) <= x && x <= ax.max(bx) && ay.min(by) <= y && y <= ay.max(by)
+        {
+            return Relation::Border;
+        }
+        // the edge straddles the horizontal through the point, right of it
+        if (ay > y) != (by > y) && (cross > zero) == (by > ay) {
+            inside = !inside;
+        }
+    }
+    if inside {
+        Relation::Inside
+    } else {
+        Relation::Outside
+    }
+}
+
+pub fn polygon_calc_f64(x: f64, y: f64, vertices: &[(f64, f64)], eps: f64) -> Relation {
+    let mut inside = false;
+    for (i, &(ax, ay)) in vertices.iter().enumerate() {
+        let (bx, by) = vertices[(i + 1) % vertices.len()];
+        let (dx, dy) = (bx - ax, by - ay);
+        let length = dx * dx + dy * dy;
+        let t = if length > 0.0 {
+            (((x - ax) * dx + (y - ay) * dy) / length).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        if (x - ax - t * dx).hypot(y - ay - t * dy) <= eps {
+            return Relation::Border;
+        }
+        if (ay > y) != (by > y) && x < ax + (y - ay) * dx / dy {
+            inside = !inside;
+        }
+    }
+    if inside {
+        Relation::Inside
+    } else {
+        Relation::Outside
+    }
+}
+
+// A polygon shape, with vertices in units of the border it is compared
+// against: `[[1, 0], [0, 1], [-1, 0], [0, -1]]` with border 10 is the diamond
+// through (10, 0). Rational vertices are kept as integers over a common
+// `scale`, so the point is scaled up instead.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(try_from = "Vec<[Ratio; 2]>", into = "Vec<[Ratio; 2]>")]
+pub struct Polygon {
+    vertices: Vec<[Ratio; 2]>,
+    scale: i128,
+    lattice: Vec<(i128, i128)>,
+}
+
+impl TryFrom<Vec<[Ratio; 2]>> for Polygon {
+    type Error = String;
+
+    fn try_from(vertices: Vec<[Ratio; 2]>) -> Result<Self, String> {
+        if vertices.len() < 3 {
+            return Err("a polygon needs at least 3 vertices".to_string());
+        }
+        let overflow = || "polygon vertices are too precise".to_string();
+        let mut scale: i128 = 1;
+        for c in vertices.iter().flatten() {
+            scale = (scale / gcd(scale, c.den()))
+                .checked_mul(c.den())
+                .ok_or_else(overflow)?;
+        }
+        let lift = |c: Ratio| c.num().checked_mul(scale / c.den()).ok_or_else(overflow);
+        let lattice = vertices
+            .iter()
+            .map(|&[x, y]| Ok((lift(x)?, lift(y)?)))
+            .collect::<Result<_, String>>()?;
+        Ok(Polygon {
+            vertices,
+            scale,
+            lattice,
+        })
+    }
+}
+
+impl From<Polygon> for Vec<[Ratio; 2]> {
+    fn from(polygon: Polygon) -> Self {
+        polygon.vertices
+    }
+}
+
+impl Polygon {
+    pub fn relation<T: Exact>(&self, x: T, y: T, border: T) -> Relation {
+        let scale = T::from(self.scale);
+        let vertices: Vec<(T, T)> = self
+            .lattice
+            .iter()
+            .map(|&(vx, vy)| (T::from(vx) * border, T::from(vy) * border))
+            .collect();
+        polygon_calc(x * scale, y * scale, &vertices)
+    }
+
+    pub fn relation_f64(&self, x: f64, y: f64, border: f64, eps: f64) -> Relation {
+        polygon_calc_f64(x, y, &self.points(border), eps)
+    }
+
+    pub fn scale(&self) -> i128 {
+        self.scale
+    }
+
+    pub fn lattice(&self) -> &[(i128, i128)] {
+        &self.lattice
+    }
+
+    pub fn points(&self, border: f64) -> Vec<(f64, f64)> {
+        let real = |c: Ratio| c.num() as f64 / c.den() as f64 * border;
+        self.vertices
+            .iter()
+            .map(|&[x, y]| (real(x), real(y)))
+            .collect()
+    }
+
+    // Coordinates are scaled by `scale` and vertices reach `border` times the
+    // largest lattice coordinate; the edge test multiplies two differences of
+    // those and adds two such products.
+    pub fn magnitude(&self, reach: i128) -> Option<i128> {
+        let extent = self
+            .lattice
+            .iter()
+            .map(|&(x, y)| x.abs().max(y.abs()))
+            .fold(self.scale, i128::max);
+        let span = reach.checked_mul(extent)?.checked_mul(2)?;
+        span.checked_mul(span)?.checked_mul(2)
+    }
+}
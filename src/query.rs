@@ -0,0 +1,277 @@
+use crate::errors::{ErrorCode, FieldError};
+use crate::figure::{Bounds, MyPoint, Range};
+use crate::grid::{Grid, GridFormat, MAX_GRID_CELLS};
+use crate::ratio::Ratio;
+use crate::render::{self, Palette, MAX_PNG_SCALE};
+use crate::spec::{Arithmetic, FigureSpec};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use warp::Filter;
+
+pub struct Fields(HashMap<String, String>);
+
+impl Fields {
+    pub fn new(
+        pairs: impl IntoIterator<Item = (String, String)>,
+        known: &[&str],
+    ) -> Result<Self, FieldError> {
+        let mut fields = HashMap::new();
+        for (field, value) in pairs {
+            if !known.contains(&field.as_str()) {
+                return Err(FieldError::new(ErrorCode::UnknownField, &field));
+            }
+            if fields.contains_key(&field) {
+                return Err(FieldError::new(ErrorCode::InvalidValue, &field));
+            }
+            fields.insert(field, value);
+        }
+        Ok(Fields(fields))
+    }
+
+    // A field given twice is `invalid_value`, as is a query that does not
+    // decode at all (reported on the field `query`).
+    pub fn parse(raw: &str, known: &[&str]) -> Result<Self, FieldError> {
+        let pairs: Vec<(String, String)> = serde_urlencoded::from_str(raw)
+            .map_err(|_| FieldError::new(ErrorCode::InvalidValue, "query"))?;
+        Fields::new(pairs, known)
+    }
+
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.0.get(field).map(String::as_str)
+    }
+
+    pub fn coord(&self, field: &str, bounds: Bounds) -> Result<i32, FieldError> {
+        let value = self
+            .get(field)
+            .ok_or_else(|| FieldError::new(ErrorCode::MissingField, field).with_range(bounds))?;
+        parse_bounded(field, value, bounds).map_err(|e| e.with_range(bounds))
+    }
+
+    pub fn flag(&self, field: &str) -> Result<bool, FieldError> {
+        Ok(self.optional_flag(field)?.unwrap_or(false))
+    }
+
+    pub fn optional_flag(&self, field: &str) -> Result<Option<bool>, FieldError> {
+        match self.get(field) {
+            None => Ok(None),
+            Some("false") | Some("0") => Ok(Some(false)),
+            Some("true") | Some("1") | Some("") => Ok(Some(true)),
+            Some(_) => Err(FieldError::new(ErrorCode::InvalidValue, field)),
+        }
+    }
+
+    pub fn real(&self, field: &str, bounds: Bounds) -> Result<f64, FieldError> {
+        let value = self
+            .get(field)
+            .ok_or_else(|| FieldError::new(ErrorCode::MissingField, field).with_range(bounds))?;
+        let c: f64 = match value.parse::<f64>() {
+            Ok(c) if c.is_finite() => c,
+            _ => return Err(FieldError::new(ErrorCode::NotANumber, field).with_range(bounds)),
+        };
+        if c < f64::from(bounds.lo()) || c > f64::from(bounds.hi()) {
+            return Err(FieldError::new(ErrorCode::OutOfRange, field).with_range(bounds));
+        }
+        Ok(c)
+    }
+
+    pub fn ratio(&self, field: &str, bounds: Bounds) -> Result<Ratio, FieldError> {
+        let value = self
+            .get(field)
+            .ok_or_else(|| FieldError::new(ErrorCode::MissingField, field).with_range(bounds))?;
+        let c = value
+            .parse::<Ratio>()
+            .map_err(|code| FieldError::new(code, field).with_range(bounds))?;
+        if c < Ratio::from(bounds.lo()) || c > Ratio::from(bounds.hi()) {
+            return Err(FieldError::new(ErrorCode::OutOfRange, field).with_range(bounds));
+        }
+        Ok(c)
+    }
+
+    pub fn optional_int(
+        &self,
+        field: &str,
+        default: i32,
+        min: i32,
+        max: i32,
+    ) -> Result<i32, FieldError> {
+        let bounds = Bounds::new(min, max);
+        match self.get(field) {
+            Some(value) => parse_bounded(field, value, bounds).map_err(|e| e.with_range(bounds)),
+            None => Ok(default),
+        }
+    }
+}
+
+fn parse_bounded(field: &str, value: &str, bounds: Bounds) -> Result<i32, FieldError> {
+    let c: i128 = value
+        .parse()
+        .map_err(|_| FieldError::new(ErrorCode::NotANumber, field))?;
+    if !bounds.contains(c) {
+        return Err(FieldError::new(ErrorCode::OutOfRange, field));
+    }
+    Ok(c as i32)
+}
+
+pub fn raw() -> impl Filter<Extract = (String,), Error = Infallible> + Clone {
+    warp::query::raw().or(warp::any().map(String::new)).unify()
+}
+
+pub struct PointQuery {
+    pub point: MyPoint,
+    pub explain: bool,
+}
+
+pub fn parse_point(raw: &str, range: &Range) -> Result<PointQuery, FieldError> {
+    let fields = Fields::parse(raw, &["x", "y", "explain"])?;
+    Ok(PointQuery {
+        point: point_from_fields(&fields, range)?,
+        explain: fields.flag("explain")?,
+    })
+}
+
+pub fn parse_xy(raw: &str, range: &Range) -> Result<MyPoint, FieldError> {
+    point_from_fields(&Fields::parse(raw, &["x", "y"])?, range)
+}
+
+fn point_from_fields(fields: &Fields, range: &Range) -> Result<MyPoint, FieldError> {
+    Ok(MyPoint {
+        x: fields.coord("x", range.x)?,
+        y: fields.coord("y", range.y)?,
+    })
+}
+
+// Batch items are validated like query strings, so a JSON number `5` and the
+// string `"5"` are both accepted and anything else is `not_a_number`.
+pub fn point_from_json(value: &serde_json::Value, range: &Range) -> Result<MyPoint, FieldError> {
+    let fields = value
+        .as_object()
+        .ok_or_else(|| FieldError::new(ErrorCode::MissingField, "x"))?;
+    let pairs = fields.iter().map(|(field, value)| {
+        let value = match value {
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        (field.clone(), value)
+    });
+    point_from_fields(&Fields::new(pairs, &["x", "y"])?, range)
+}
+
+// A path needs at least two points; an invalid one is reported with its
+// index, e.g. field `[2].y`.
+pub fn parse_path(values: &[serde_json::Value], range: &Range) -> Result<Vec<MyPoint>, FieldError> {
+    if values.len() < 2 {
+        let field = format!("[{}]", values.len());
+        return Err(FieldError::new(ErrorCode::MissingField, &field));
+    }
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            point_from_json(value, range).map_err(|e| FieldError {
+                field: format!("[{}].{}", i, e.field),
+                ..e
+            })
+        })
+        .collect()
+}
+
+pub fn parse_grid(raw: &str, range: &Range) -> Result<(Grid, GridFormat), FieldError> {
+    let fields = Fields::parse(raw, &["x0", "x1", "y0", "y1", "step", "format"])?;
+    let grid = Grid::new(
+        (fields.coord("x0", range.x)?, fields.coord("x1", range.x)?),
+        (fields.coord("y0", range.y)?, fields.coord("y1", range.y)?),
+        fields.optional_int("step", 1, 1, i32::MAX)?,
+    );
+    if grid.cells() > MAX_GRID_CELLS {
+        return Err(FieldError::new(ErrorCode::TooManyCells, "step"));
+    }
+    let format = match fields.get("format") {
+        None | Some("text") => GridFormat::Text,
+        Some("json") => GridFormat::Json,
+        Some(_) => return Err(FieldError::new(ErrorCode::InvalidValue, "format")),
+    };
+    Ok((grid, format))
+}
+
+pub fn parse_png(raw: &str, range: &Range) -> Result<(i32, Palette), FieldError> {
+    let fields = Fields::parse(raw, &["scale", "inside", "border", "outside"])?;
+    if render::png_grid(range).cells() > MAX_GRID_CELLS {
+        return Err(FieldError::new(ErrorCode::TooManyCells, "range"));
+    }
+    let scale = fields.optional_int("scale", 2, 1, MAX_PNG_SCALE)?;
+    let mut palette = Palette::default();
+    for (field, colour) in [
+        ("inside", &mut palette.inside),
+        ("border", &mut palette.border),
+        ("outside", &mut palette.outside),
+    ] {
+        if let Some(value) = fields.get(field) {
+            *colour = parse_colour(value)
+                .ok_or_else(|| FieldError::new(ErrorCode::InvalidValue, field))?;
+        }
+    }
+    Ok((scale, palette))
+}
+
+// `rrggbb`, with or without a leading `#`.
+fn parse_colour(value: &str) -> Option<[u8; 3]> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+pub fn parse_ascii(raw: &str, range: &Range) -> Result<i32, FieldError> {
+    let fields = Fields::parse(raw, &["scale"])?;
+    let default = render::ascii_step(range, render::ASCII_COLUMNS);
+    let step = fields.optional_int("scale", default, 1, i32::MAX)?;
+    if render::ascii_grid(range, step).cells() > MAX_GRID_CELLS {
+        return Err(FieldError::new(ErrorCode::TooManyCells, "scale"));
+    }
+    Ok(step)
+}
+
+pub enum RealQuery {
+    Float { x: f64, y: f64, eps: f64 },
+    Exact { x: Ratio, y: Ratio },
+}
+
+// `exact` overrides the figure's `arithmetic`. Exact coordinates are decimals
+// or fractions such as `15/2`.
+pub fn parse_real(raw: &str, spec: &FigureSpec) -> Result<RealQuery, FieldError> {
+    let fields = Fields::parse(raw, &["x", "y", "eps", "exact"])?;
+    let range = spec.range;
+    let exact = match fields.optional_flag("exact")? {
+        Some(exact) => exact,
+        None => matches!(spec.arithmetic, Arithmetic::Exact) && fields.get("eps").is_none(),
+    };
+    if !exact {
+        let eps = match fields.get("eps") {
+            Some(value) => match value.parse::<f64>() {
+                Ok(eps) if eps.is_finite() && eps >= 0.0 => eps,
+                Ok(_) => return Err(FieldError::new(ErrorCode::OutOfRange, "eps")),
+                Err(_) => return Err(FieldError::new(ErrorCode::NotANumber, "eps")),
+            },
+            None => 0.0,
+        };
+        return Ok(RealQuery::Float {
+            x: fields.real("x", range.x)?,
+            y: fields.real("y", range.y)?,
+            eps,
+        });
+    }
+    if fields.get("eps").is_some() {
+        return Err(FieldError::new(ErrorCode::InvalidValue, "eps"));
+    }
+
+    let x = fields.ratio("x", range.x)?;
+    let y = fields.ratio("y", range.y)?;
+    for (field, value, other) in [("x", x, y), ("y", y, x)] {
+        if !spec.ratio_fits(value, other) {
+            return Err(FieldError::new(ErrorCode::InvalidValue, field));
+        }
+    }
+    Ok(RealQuery::Exact { x, y })
+}
@@ -0,0 +1,190 @@
+use crate::errors::ErrorCode;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use serde_derive::Deserialize;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg};
+use std::str::FromStr;
+
+// `num / den` in lowest terms with `den > 0`, so equal values compare equal
+// field by field. The arithmetic is plain `i128`; callers keep operands small
+// enough (see `query::parse_real`) for the products to stay exact.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ratio {
+    num: i128,
+    den: i128,
+}
+
+pub fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+impl Ratio {
+    pub fn new(num: i128, den: i128) -> Self {
+        assert!(den != 0, "zero denominator");
+        let g = gcd(num, den) * den.signum();
+        Ratio {
+            num: num / g,
+            den: den / g,
+        }
+    }
+
+    pub fn num(self) -> i128 {
+        self.num
+    }
+
+    pub fn den(self) -> i128 {
+        self.den
+    }
+}
+
+impl Default for Ratio {
+    fn default() -> Self {
+        Ratio { num: 0, den: 1 }
+    }
+}
+
+impl From<i32> for Ratio {
+    fn from(n: i32) -> Self {
+        Ratio {
+            num: n.into(),
+            den: 1,
+        }
+    }
+}
+
+impl From<i128> for Ratio {
+    fn from(n: i128) -> Self {
+        Ratio { num: n, den: 1 }
+    }
+}
+
+impl Ord for Ratio {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.num * other.den).cmp(&(other.num * self.den))
+    }
+}
+
+impl PartialOrd for Ratio {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for Ratio {
+    type Output = Ratio;
+
+    fn add(self, other: Ratio) -> Ratio {
+        Ratio::new(
+            self.num * other.den + other.num * self.den,
+            self.den * other.den,
+        )
+    }
+}
+
+impl Mul for Ratio {
+    type Output = Ratio;
+
+    fn mul(self, other: Ratio) -> Ratio {
+        Ratio::new(self.num * other.num, self.den * other.den)
+    }
+}
+
+impl Neg for Ratio {
+    type Output = Ratio;
+
+    fn neg(self) -> Ratio {
+        Ratio {
+            num: -self.num,
+            den: self.den,
+        }
+    }
+}
+
+impl fmt::Display for Ratio {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.den == 1 {
+            write!(formatter, "{}", self.num)
+        } else {
+            write!(formatter, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+// In figure files a ratio is an integer or a string such as `"1/3"` or `"2.5"`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Repr {
+    Integer(i64),
+    Text(String),
+}
+
+impl<'de> Deserialize<'de> for Ratio {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Repr::deserialize(deserializer)? {
+            Repr::Integer(n) => Ok(Ratio::new(n.into(), 1)),
+            Repr::Text(text) => text
+                .parse()
+                .map_err(|_| de::Error::custom(format!("invalid number `{}`", text))),
+        }
+    }
+}
+
+impl Serialize for Ratio {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match i64::try_from(self.num) {
+            Ok(n) if self.den == 1 => serializer.serialize_i64(n),
+            _ => serializer.collect_str(self),
+        }
+    }
+}
+
+const MAX_DECIMAL_DIGITS: usize = 18;
+
+// A decimal, or a fraction of two integers like `-15/2`.
+impl FromStr for Ratio {
+    type Err = ErrorCode;
+
+    fn from_str(value: &str) -> Result<Self, ErrorCode> {
+        let Some((num, den)) = value.split_once('/') else {
+            let (mantissa, digits) = parse_decimal(value)?;
+            return Ok(Ratio::new(mantissa, 10i128.pow(digits)));
+        };
+        match (parse_decimal(num)?, parse_decimal(den)?) {
+            (_, (0, _)) => Err(ErrorCode::InvalidValue),
+            ((num, 0), (den, 0)) => Ok(Ratio::new(num, den)),
+            _ => Err(ErrorCode::NotANumber),
+        }
+    }
+}
+
+// `-12.5` parses to `(-125, 1)`: the value is `mantissa / 10^digits`.
+fn parse_decimal(value: &str) -> Result<(i128, u32), ErrorCode> {
+    let (negative, unsigned) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if whole.len() + fraction.len() == 0 || !all_digits(whole) || !all_digits(fraction) {
+        return Err(ErrorCode::NotANumber);
+    }
+    let whole = whole.trim_start_matches('0');
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > MAX_DECIMAL_DIGITS {
+        return Err(ErrorCode::InvalidValue);
+    }
+    if whole.len() > 12 {
+        return Err(ErrorCode::OutOfRange);
+    }
+    let mantissa: i128 = format!("0{}{}", whole, fraction)
+        .parse()
+        .map_err(|_| ErrorCode::NotANumber)?;
+    let digits = fraction.len() as u32;
+    Ok((if negative { -mantissa } else { mantissa }, digits))
+}
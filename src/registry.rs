@@ -0,0 +1,128 @@
+use crate::csg::Composite;
+use crate::expr::Formula;
+use crate::figure::{Range, Relation};
+use crate::spec::{FigureFile, FigureSpec};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+pub trait Figure: Send + Sync {
+    fn name(&self) -> &str;
+    fn locate(&self, x: i32, y: i32) -> Relation;
+    fn metadata(&self) -> serde_json::Value;
+
+    fn range(&self) -> Range {
+        Range::default()
+    }
+
+    fn spec(&self) -> Option<&FigureSpec> {
+        None
+    }
+}
+
+impl Figure for FigureSpec {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn locate(&self, x: i32, y: i32) -> Relation {
+        FigureSpec::locate(self, x, y)
+    }
+
+    fn metadata(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    fn range(&self) -> Range {
+        self.range
+    }
+
+    fn spec(&self) -> Option<&FigureSpec> {
+        Some(self)
+    }
+}
+
+impl Figure for Composite {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn locate(&self, x: i32, y: i32) -> Relation {
+        self.region.relation(i128::from(x), i128::from(y))
+    }
+
+    fn metadata(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    fn range(&self) -> Range {
+        self.range
+    }
+}
+
+#[derive(Default)]
+pub struct Registry {
+    figures: BTreeMap<String, Arc<dyn Figure>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, figure: Arc<dyn Figure>) {
+        self.figures.insert(figure.name().to_string(), figure);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Figure>> {
+        self.figures.get(name).cloned()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.figures.keys().cloned().collect()
+    }
+
+    pub fn figures(&self) -> impl Iterator<Item = &Arc<dyn Figure>> {
+        self.figures.values()
+    }
+}
+
+impl From<Vec<FigureSpec>> for Registry {
+    fn from(specs: Vec<FigureSpec>) -> Self {
+        let mut registry = Registry::new();
+        for spec in specs {
+            registry.register(Arc::new(spec));
+        }
+        registry
+    }
+}
+
+impl Figure for Formula {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn locate(&self, x: i32, y: i32) -> Relation {
+        self.expression.relation(i128::from(x), i128::from(y))
+    }
+
+    fn metadata(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    fn range(&self) -> Range {
+        self.range
+    }
+}
+
+impl From<FigureFile> for Registry {
+    fn from(file: FigureFile) -> Self {
+        let mut registry = Registry::from(file.figure);
+        for composite in file.composite {
+            registry.register(Arc::new(composite));
+        }
+        for formula in file.formula {
+            registry.register(Arc::new(formula));
+        }
+        registry
+    }
+}
@@ -0,0 +1,299 @@
+use crate::figure::{Bounds, Range, Relation};
+use crate::grid::{self, Grid, MAX_GRID_CELLS};
+use crate::registry::Figure;
+use crate::spec::{Band, FigureSpec, Shape};
+use std::fmt::Write;
+use std::iter;
+
+const MARGIN: i32 = 5;
+pub const MAX_PNG_SCALE: i32 = 8;
+
+const LP_SAMPLES: usize = 256;
+
+fn polygon(points: impl IntoIterator<Item = (f64, f64)>) -> String {
+    let points: Vec<String> = points
+        .into_iter()
+        .map(|(x, y)| format!("{:.3},{:.3}", x, y))
+        .collect();
+    format!("M{} Z", points.join(" L"))
+}
+
+fn shape_path(shape: &Shape, border: i32) -> String {
+    let r = f64::from(border);
+    match shape {
+        Shape::Box => format!("M{0},{0} H{1} V{1} H{0} Z", -border, border),
+        Shape::Circle => format!(
+            "M{0},0 A{0},{0} 0 1,0 {1},0 A{0},{0} 0 1,0 {0},0 Z",
+            border, -border
+        ),
+        Shape::Diamond => format!("M{0},0 L0,{0} L{1},0 L0,{1} Z", border, -border),
+        Shape::Ellipse([a, b]) => {
+            let (ra, rb) = (r * f64::from(*a), r * f64::from(*b));
+            format!(
+                "M{0},0 A{0},{1} 0 1,0 {2},0 A{0},{1} 0 1,0 {0},0 Z",
+                ra, rb, -ra
+            )
+        }
+        // the superellipse `|x|^p + |y|^p = r^p`, sampled by angle
+        Shape::Lp(p) => {
+            let e = 2.0 / f64::from(*p);
+            polygon((0..LP_SAMPLES).map(|i| {
+                let t = std::f64::consts::TAU * i as f64 / LP_SAMPLES as f64;
+                let (sin, cos) = t.sin_cos();
+                (
+                    r * cos.signum() * cos.abs().powf(e),
+                    r * sin.signum() * sin.abs().powf(e),
+                )
+            }))
+        }
+        Shape::RotatedBox([c, s]) => {
+            let (c, s) = (f64::from(*c), f64::from(*s));
+            let n = c.hypot(s);
+            let (ux, uy) = (r * c / n, r * s / n);
+            polygon([
+                (ux - uy, uy + ux),
+                (-ux - uy, -uy + ux),
+                (-ux + uy, -uy - ux),
+                (ux + uy, uy - ux),
+            ])
+        }
+        Shape::Polygon(vertices) => polygon(vertices.points(r)),
+    }
+}
+
+// Each quadrant or sector is drawn on its own: every "inside" ring is the
+// shape of its border masked by the one before it (or the whole part masked by
+// the last border, for an inside ring beyond it), then all borders are
+// stroked, clipped to the part so the picture shows exactly what `ring`
+// computes there.
+fn part(svg: &mut String, spec: &FigureSpec, id: &str, band: &Band, clip: &str) {
+    let paths: Vec<String> = (0..spec.border_count())
+        .map(|k| shape_path(band.shape(k), spec.border(k)))
+        .collect();
+    let rect = clip.to_string();
+
+    let _ = write!(
+        svg,
+        r#"<clipPath id="clip-{id}"><path d="{clip}"/></clipPath>"#
+    );
+    let mut fills = String::new();
+    for k in (1..=paths.len()).step_by(2) {
+        let mask = match k {
+            1 => format!("mask-{id}"),
+            _ => format!("mask-{id}-{k}"),
+        };
+        let (outer, inner) = (paths.get(k).unwrap_or(&rect), &paths[k - 1]);
+        let _ = write!(
+            svg,
+            r#"<mask id="{mask}"><path d="{outer}" fill="white"/><path d="{inner}" fill="black"/></mask>"#
+        );
+        let _ = write!(
+            fills,
+            r#"<path class="inside" d="{outer}" mask="url(#{mask})"/>"#
+        );
+    }
+    let _ = write!(svg, r#"<g clip-path="url(#clip-{id})">{fills}"#);
+    for path in &paths {
+        let _ = write!(svg, r#"<path class="border" d="{path}"/>"#);
+    }
+    svg.push_str("</g>");
+}
+
+// The wedge from the origin counter-clockwise between two angles, reaching
+// past the corners of the picture; equal angles give the whole turn.
+fn wedge(from: f64, to: f64, extent: i32) -> String {
+    let mut sweep = (to - from).rem_euclid(360.0);
+    if sweep == 0.0 {
+        sweep = 360.0;
+    }
+    let steps = (sweep / 45.0).ceil() as usize;
+    let r = 2.0 * f64::from(extent);
+    polygon(iter::once((0.0, 0.0)).chain((0..=steps).map(|i| {
+        let (sin, cos) = (from + sweep * i as f64 / steps as f64)
+            .to_radians()
+            .sin_cos();
+        (r * cos, r * sin)
+    })))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// The picture covers the figure's coordinate range, or twice its largest
+// border when the range is unbounded.
+fn extent(spec: &FigureSpec) -> i32 {
+    let range = spec.range;
+    let bounds = [range.x.min, range.x.max, range.y.min, range.y.max];
+    let reach = match bounds
+        .iter()
+        .map(|b| b.map(i32::unsigned_abs))
+        .collect::<Option<Vec<_>>>()
+    {
+        Some(reach) => reach.into_iter().max().unwrap_or_default(),
+        None => 2 * spec.max_border().unsigned_abs(),
+    };
+    reach.saturating_add(MARGIN as u32).min(i32::MAX as u32 / 2) as i32
+}
+
+pub fn svg(spec: &FigureSpec) -> String {
+    let extent = extent(spec);
+    let size = 2 * extent;
+    let mut svg = String::new();
+
+    let _ = write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{0} {0} {1} {1}" width="{2}" height="{2}">"#,
+        -extent,
+        size,
+        i64::from(size) * 3
+    );
+    let _ = write!(
+        svg,
+        "<style>.inside{{fill:#9ecae1}}.border{{fill:none;stroke:#08519c;stroke-width:0.5}}.axis{{stroke:#636363;stroke-width:0.3}}</style>"
+    );
+    let _ = write!(svg, r#"<title>{}</title>"#, escape(&spec.name));
+    svg.push_str(r#"<g transform="scale(1,-1)">"#);
+
+    if let Some(q) = &spec.quadrants {
+        for (id, band, (sx, sy)) in [
+            ("upper-right", &q.upper_right, (1, 1)),
+            ("lower-right", &q.lower_right, (1, -1)),
+            ("upper-left", &q.upper_left, (-1, 1)),
+            ("lower-left", &q.lower_left, (-1, -1)),
+        ] {
+            let (x, y) = (sx.min(0) * extent, sy.min(0) * extent);
+            let rect = format!("M{x},{y} h{extent} v{extent} h{} Z", -extent);
+            part(&mut svg, spec, id, band, &rect);
+        }
+    }
+    for (k, sector) in spec.sectors.iter().enumerate() {
+        let next = spec.sectors[(k + 1) % spec.sectors.len()].from;
+        let wedge = wedge(sector.from.degrees(), next.degrees(), extent);
+        part(&mut svg, spec, &format!("sector-{k}"), &sector.band, &wedge);
+    }
+
+    let _ = write!(
+        svg,
+        r#"<line class="axis" x1="{0}" y1="0" x2="{1}" y2="0"/><line class="axis" x1="0" y1="{0}" x2="0" y2="{1}"/>"#,
+        -extent, extent
+    );
+    svg.push_str("</g></svg>");
+    svg
+}
+
+pub struct Palette {
+    pub inside: [u8; 3],
+    pub border: [u8; 3],
+    pub outside: [u8; 3],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            inside: [0x9e, 0xca, 0xe1],
+            border: [0x08, 0x51, 0x9c],
+            outside: [0xff, 0xff, 0xff],
+        }
+    }
+}
+
+impl Palette {
+    fn colour(&self, relation: &Relation) -> [u8; 3] {
+        match relation {
+            Relation::Inside => self.inside,
+            Relation::Border => self.border,
+            Relation::Outside => self.outside,
+        }
+    }
+}
+
+pub fn png_grid(range: &Range) -> Grid {
+    let (x, y) = (range.x, range.y);
+    Grid::new((x.lo(), x.hi()), (y.lo(), y.hi()), 1)
+}
+
+// One `scale` x `scale` block per lattice point of the coordinate range, top
+// row first.
+pub fn png(figure: &dyn Figure, scale: i32, palette: &Palette) -> Vec<u8> {
+    let grid = png_grid(&figure.range());
+    let rows = grid.classify(figure);
+    let scale = scale as usize;
+    let width = rows[0].len() * scale;
+    let height = rows.len() * scale;
+
+    let mut data = Vec::with_capacity(width * height * 3);
+    for row in &rows {
+        let line: Vec<u8> = row
+            .iter()
+            .flat_map(|relation| iter::repeat_n(palette.colour(relation), scale))
+            .flatten()
+            .collect();
+        for _ in 0..scale {
+            data.extend_from_slice(&line);
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .expect("encoding a PNG into memory");
+    out
+}
+
+pub const ASCII_COLUMNS: usize = 80;
+
+// The coarsest step that still fits `columns` characters per line, made
+// coarser still until the picture has at most `MAX_GRID_CELLS` characters;
+// samples stay on multiples of the step so the axes are always drawn.
+pub fn ascii_step(range: &Range, columns: usize) -> i32 {
+    let span = |bounds: Bounds| i64::from(bounds.hi()) - i64::from(bounds.lo());
+    let mut step = (span(range.x) / columns.max(1) as i64).clamp(1, i32::MAX.into()) as i32;
+    let count = |step: i32| {
+        let (lo, hi) = grid::aligned(range.x, step);
+        ((i64::from(hi) - i64::from(lo)) / i64::from(step) + 1) as usize
+    };
+    while step < i32::MAX && count(step) > columns {
+        step += 1;
+    }
+    // no step below these fits: a single column still has a row per step,
+    // and the cells shrink with the square of the step
+    let cells = MAX_GRID_CELLS as f64;
+    let tall = span(range.y) as f64 / cells;
+    let area = ((span(range.x) as f64 + 1.0) * (span(range.y) as f64 + 1.0) / cells).sqrt();
+    step = step.max(tall.max(area).min(f64::from(i32::MAX)) as i32);
+    while step < i32::MAX && ascii_grid(range, step).cells() > MAX_GRID_CELLS {
+        step += 1;
+    }
+    step
+}
+
+pub fn ascii_grid(range: &Range, step: i32) -> Grid {
+    Grid::new(
+        grid::aligned(range.x, step),
+        grid::aligned(range.y, step),
+        step,
+    )
+}
+
+pub fn ascii(figure: &dyn Figure, step: i32) -> String {
+    let grid = ascii_grid(&figure.range(), step);
+    let mut text = String::new();
+    for y in grid.lines() {
+        text.extend(grid.columns().map(|x| match figure.locate(x, y) {
+            Relation::Inside => '#',
+            Relation::Border => '+',
+            Relation::Outside if x == 0 => '|',
+            Relation::Outside if y == 0 => '-',
+            Relation::Outside => '.',
+        }));
+        text.push('\n');
+    }
+    text
+}
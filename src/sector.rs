@@ -0,0 +1,167 @@
+use crate::ratio::gcd;
+use crate::spec::Band;
+use serde_derive::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::ops::{Add, Mul, Neg};
+
+// Directions are integer vectors so that a point on a ray is found by an exact
+// cross product. Angles in degrees are only taken in multiples of 45°, which
+// have such a vector; a vector such as `[1, 2]` gives any other rational
+// slope.
+const EIGHTHS: [(i32, i32); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+const MAX_COMPONENT: i32 = 1_000_000;
+
+#[derive(Deserialize, Serialize, Clone, Copy)]
+#[serde(untagged)]
+pub enum Ray {
+    Degrees(f64),
+    Vector([i32; 2]),
+}
+
+impl Ray {
+    pub fn direction(self) -> (i32, i32) {
+        let (x, y) = match self {
+            Ray::Degrees(degrees) => EIGHTHS[(degrees / 45.0).round().rem_euclid(8.0) as usize],
+            Ray::Vector([x, y]) => (x, y),
+        };
+        let g = gcd(x.into(), y.into()).max(1) as i32;
+        (x / g, y / g)
+    }
+
+    pub fn degrees(self) -> f64 {
+        let (x, y) = self.direction();
+        f64::from(y).atan2(f64::from(x)).to_degrees()
+    }
+
+    fn check(self) -> Result<(), String> {
+        match self {
+            Ray::Degrees(degrees) if !degrees.is_finite() => {
+                Err("sector angles must be finite".to_string())
+            }
+            Ray::Degrees(degrees) if degrees % 45.0 != 0.0 => Err(format!(
+                "sector angle {} is not a multiple of 45 degrees; give a vector such as [1, 2] instead",
+                degrees
+            )),
+            Ray::Vector([0, 0]) => Err("a sector ray needs a non-zero vector".to_string()),
+            Ray::Vector([x, y])
+                if x.unsigned_abs().max(y.unsigned_abs()) > MAX_COMPONENT as u32 =>
+            {
+                Err(format!(
+                    "sector ray components must be at most {}",
+                    MAX_COMPONENT
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// A sector runs counter-clockwise from its own ray, which it owns, up to the
+// next sector's ray; the last one wraps around to the first.
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sector {
+    pub from: Ray,
+    #[serde(flatten)]
+    pub band: Band,
+}
+
+pub trait Coordinate: Copy + PartialOrd + Default + From<i32>
+where
+    Self: Add<Output = Self> + Mul<Output = Self> + Neg<Output = Self>,
+{
+}
+
+impl<T> Coordinate for T
+where
+    T: Copy + PartialOrd + Default + From<i32>,
+    T: Add<Output = T> + Mul<Output = T> + Neg<Output = T>,
+{
+}
+
+fn cross<T: Coordinate>((ax, ay): (T, T), (bx, by): (T, T)) -> T {
+    ax * by + -(ay * bx)
+}
+
+// `v` in the frame where `a` points along the positive x axis (scaled by
+// `|a|^2`, which keeps every angle).
+fn relative<T: Coordinate>(a: (T, T), v: (T, T)) -> (T, T) {
+    (a.0 * v.0 + a.1 * v.1, cross(a, v))
+}
+
+// Counter-clockwise order of directions from the positive x axis: the upper
+// half-plane with the positive axis first, then the rest, each ordered by
+// the sign of the cross product.
+fn angle_cmp<T: Coordinate>(a: (T, T), b: (T, T)) -> Ordering {
+    let zero = T::default();
+    let lower = |(x, y): (T, T)| !(y > zero || (y == zero && x > zero));
+    lower(a)
+        .cmp(&lower(b))
+        .then_with(|| zero.partial_cmp(&cross(a, b)).unwrap_or(Ordering::Equal))
+}
+
+fn direction<T: Coordinate>(ray: Ray) -> (T, T) {
+    let (x, y) = ray.direction();
+    (T::from(x), T::from(y))
+}
+
+// The index of the sector holding `(x, y)`; the origin is in the first one.
+pub fn select<T: Coordinate>(sectors: &[Sector], x: T, y: T) -> usize {
+    let zero = T::default();
+    if x == zero && y == zero {
+        return 0;
+    }
+    let start = direction(sectors[0].from);
+    let point = relative(start, (x, y));
+    sectors
+        .iter()
+        .rposition(|sector| {
+            angle_cmp(relative(start, direction(sector.from)), point) != Ordering::Greater
+        })
+        .unwrap_or(0)
+}
+
+pub fn check(sectors: &[Sector]) -> Result<(), String> {
+    sectors.iter().try_for_each(|sector| sector.from.check())?;
+    let Some(first) = sectors.first() else {
+        return Ok(());
+    };
+    let start = direction::<i128>(first.from);
+    let turns: Vec<_> = sectors
+        .iter()
+        .map(|sector| relative(start, direction(sector.from)))
+        .collect();
+    if turns
+        .windows(2)
+        .any(|pair| angle_cmp(pair[0], pair[1]) != Ordering::Less)
+    {
+        return Err(
+            "sector rays must be distinct and go counter-clockwise within one turn".to_string(),
+        );
+    }
+    Ok(())
+}
+
+// Rays are turned into the frame of the first one and the point's turned
+// coordinates are crossed with theirs: three ray components and one
+// coordinate per term.
+pub fn magnitude(sectors: &[Sector], reach: i128) -> Option<i128> {
+    let extent = sectors
+        .iter()
+        .map(|sector| {
+            let (x, y) = sector.from.direction();
+            i128::from(x.unsigned_abs().max(y.unsigned_abs()))
+        })
+        .max()
+        .unwrap_or_default();
+    reach.checked_mul(extent.checked_pow(3)?)?.checked_mul(8)
+}
@@ -0,0 +1,543 @@
+use crate::csg::Composite;
+use crate::expr::Formula;
+use crate::figure::{
+    box_calc, box_calc_f64, box_distance, diamond_calc, diamond_calc_f64, diamond_distance,
+    ellipse_calc, ellipse_calc_f64, ellipse_distance, lp_calc, lp_calc_f64, lp_distance,
+    radii_calc, radii_calc_f64, radii_distance, ring, rotated_box_calc, rotated_box_calc_f64,
+    rotated_box_distance, Exact, Range, Relation, Ring, MAX_MAGNITUDE,
+};
+use crate::polygon::Polygon;
+use crate::ratio::Ratio;
+use crate::sector::{self, Coordinate, Sector};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+// `box` and `circle` are the L∞ and L2 balls; the others take parameters,
+// e.g. `{ lp = 4 }`, `{ ellipse = [2, 1] }` (stretched by 2 along x),
+// `{ rotated_box = [3, 4] }` (one side along the vector (3, 4)) or
+// `{ polygon = [[1, 0], [0, 1], ["-1/2", 0]] }` (see `Polygon`).
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum Shape {
+    Box,
+    Circle,
+    Diamond,
+    Lp(u32),
+    Ellipse([i32; 2]),
+    RotatedBox([i32; 2]),
+    Polygon(Polygon),
+}
+
+impl Shape {
+    pub fn relation<T: Exact>(&self, x: T, y: T, border: T) -> Relation {
+        match self {
+            Shape::Box => box_calc(x, y, border),
+            Shape::Circle => radii_calc(x, y, border),
+            Shape::Diamond => diamond_calc(x, y, border),
+            Shape::Lp(p) => lp_calc(x, y, border, *p),
+            Shape::Ellipse([a, b]) => ellipse_calc(x, y, border, (*a, *b)),
+            Shape::RotatedBox([c, s]) => rotated_box_calc(x, y, border, (*c, *s)),
+            Shape::Polygon(polygon) => polygon.relation(x, y, border),
+        }
+    }
+
+    pub fn relation_f64(&self, x: f64, y: f64, border: f64, eps: f64) -> Relation {
+        match self {
+            Shape::Box => box_calc_f64(x, y, border, eps),
+            Shape::Circle => radii_calc_f64(x, y, border, eps),
+            Shape::Diamond => diamond_calc_f64(x, y, border, eps),
+            Shape::Lp(p) => lp_calc_f64(x, y, border, *p, eps),
+            Shape::Ellipse([a, b]) => ellipse_calc_f64(x, y, border, (*a, *b), eps),
+            Shape::RotatedBox([c, s]) => rotated_box_calc_f64(x, y, border, (*c, *s), eps),
+            Shape::Polygon(polygon) => polygon.relation_f64(x, y, border, eps),
+        }
+    }
+
+    // `None` for polygons, which are not decided by comparing two values.
+    pub fn distance(&self, x: i128, y: i128, border: i128) -> Option<(i128, i128)> {
+        Some(match self {
+            Shape::Box => box_distance(x, y, border),
+            Shape::Circle => radii_distance(x, y, border),
+            Shape::Diamond => diamond_distance(x, y, border),
+            Shape::Lp(p) => lp_distance(x, y, border, *p),
+            Shape::Ellipse([a, b]) => ellipse_distance(x, y, border, (*a, *b)),
+            Shape::RotatedBox([c, s]) => rotated_box_distance(x, y, border, (*c, *s)),
+            Shape::Polygon(_) => return None,
+        })
+    }
+
+    pub fn function(&self) -> &'static str {
+        match self {
+            Shape::Box => "box_calc",
+            Shape::Circle => "radii_calc",
+            Shape::Diamond => "diamond_calc",
+            Shape::Lp(_) => "lp_calc",
+            Shape::Ellipse(_) => "ellipse_calc",
+            Shape::RotatedBox(_) => "rotated_box_calc",
+            Shape::Polygon(_) => "polygon_calc",
+        }
+    }
+
+    // An upper bound on every value `distance` forms when the coordinates and
+    // the border are at most `reach` in absolute value; `None` if that
+    // overflows.
+    pub fn magnitude(&self, reach: i128) -> Option<i128> {
+        let square = |n: i128| n.checked_mul(n);
+        match self {
+            Shape::Box => Some(reach),
+            Shape::Diamond => reach.checked_mul(2),
+            Shape::Circle => square(reach)?.checked_mul(2),
+            Shape::Lp(p) => reach.checked_pow(*p)?.checked_mul(2),
+            Shape::Ellipse([a, b]) => {
+                let (a, b) = (i128::from(*a), i128::from(*b));
+                let sum = square(reach)?.checked_mul(square(a)?.checked_add(square(b)?)?)?;
+                let border = square(reach.checked_mul(a)?.checked_mul(b)?)?;
+                Some(sum.max(border))
+            }
+            Shape::RotatedBox([c, s]) => {
+                let (c, s) = (i128::from(*c), i128::from(*s));
+                let side = reach.checked_mul(c.abs() + s.abs())?;
+                square(side)
+            }
+            Shape::Polygon(polygon) => polygon.magnitude(reach),
+        }
+    }
+
+    pub fn fits(&self, reach: i128) -> bool {
+        self.magnitude(reach).is_some_and(|m| m <= MAX_MAGNITUDE)
+    }
+
+    pub fn check(&self) -> Result<(), String> {
+        match self {
+            Shape::Lp(0) => Err("`lp` needs p >= 1".to_string()),
+            Shape::Ellipse([a, b]) if *a < 1 || *b < 1 => {
+                Err("`ellipse` axes must be positive".to_string())
+            }
+            Shape::RotatedBox([0, 0]) => Err("`rotated_box` needs a non-zero side".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Band {
+    pub inner: Shape,
+    pub outer: Shape,
+    // one shape per border in the figure's `extra_borders`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra: Vec<Shape>,
+}
+
+impl Band {
+    pub fn shapes(&self) -> impl Iterator<Item = &Shape> {
+        [&self.inner, &self.outer].into_iter().chain(&self.extra)
+    }
+
+    pub fn shape(&self, k: usize) -> &Shape {
+        match k {
+            0 => &self.inner,
+            1 => &self.outer,
+            _ => &self.extra[k - 2],
+        }
+    }
+}
+
+// Points on the axes go where `x > 0` / `y > 0` send them, as in the
+// hand-written `point_location` functions, unless a figure's `axes` says
+// otherwise.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Quadrant {
+    UpperRight,
+    LowerRight,
+    UpperLeft,
+    LowerLeft,
+}
+
+impl Quadrant {
+    pub fn of<T: PartialOrd + Default>(x: T, y: T) -> Self {
+        match (x > T::default(), y > T::default()) {
+            (true, true) => Quadrant::UpperRight,
+            (true, false) => Quadrant::LowerRight,
+            (false, true) => Quadrant::UpperLeft,
+            (false, false) => Quadrant::LowerLeft,
+        }
+    }
+
+    // Whether `(x, y)` is in the closed quadrant, axes included.
+    pub fn touches<T: PartialOrd + Default>(self, x: T, y: T) -> bool {
+        let zero = T::default();
+        let (right, up) = match self {
+            Quadrant::UpperRight => (true, true),
+            Quadrant::LowerRight => (true, false),
+            Quadrant::UpperLeft => (false, true),
+            Quadrant::LowerLeft => (false, false),
+        };
+        (if right { x >= zero } else { x <= zero }) && (if up { y >= zero } else { y <= zero })
+    }
+}
+
+const QUADRANTS: [Quadrant; 4] = [
+    Quadrant::UpperRight,
+    Quadrant::LowerRight,
+    Quadrant::UpperLeft,
+    Quadrant::LowerLeft,
+];
+
+// What classifies a point on an axis: `sign` is the `x > 0` / `y > 0` rule of
+// `Quadrant::of`, `{ quadrant = "upper_left" }` takes that quadrant's band for
+// every axis point, `border` puts the axes on the border and `union` tries
+// every quadrant touching the point and keeps the strongest answer.
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Axes {
+    #[default]
+    Sign,
+    Quadrant(Quadrant),
+    Border,
+    Union,
+}
+
+impl Axes {
+    fn is_sign(&self) -> bool {
+        *self == Axes::Sign
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Quadrants {
+    pub upper_right: Band,
+    pub lower_right: Band,
+    pub upper_left: Band,
+    pub lower_left: Band,
+}
+
+impl Quadrants {
+    pub fn bands(&self) -> [&Band; 4] {
+        [
+            &self.upper_right,
+            &self.lower_right,
+            &self.upper_left,
+            &self.lower_left,
+        ]
+    }
+
+    pub fn band(&self, quadrant: Quadrant) -> &Band {
+        match quadrant {
+            Quadrant::UpperRight => &self.upper_right,
+            Quadrant::LowerRight => &self.lower_right,
+            Quadrant::UpperLeft => &self.upper_left,
+            Quadrant::LowerLeft => &self.lower_left,
+        }
+    }
+}
+
+// Which part of a figure's layout a point is in: `{"quadrant": "upper_right"}`
+// or `{"sector": 1}`.
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Part {
+    Quadrant(Quadrant),
+    Sector(usize),
+}
+
+// How `/real` classifies when the query does not say: `exact` compares
+// fractions exactly, `float` within `eps`.
+#[derive(Deserialize, Serialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Arithmetic {
+    #[default]
+    Float,
+    Exact,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FigureSpec {
+    pub name: String,
+    pub border_inner: i32,
+    pub border_outer: i32,
+    // further borders beyond `border_outer`, innermost first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_borders: Vec<i32>,
+    #[serde(default)]
+    pub range: Range,
+    #[serde(default)]
+    pub arithmetic: Arithmetic,
+    #[serde(default, skip_serializing_if = "Axes::is_sign")]
+    pub axes: Axes,
+    // exactly one of the two, checked on load
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quadrants: Option<Quadrants>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sectors: Vec<Sector>,
+}
+
+impl FigureSpec {
+    pub fn locate(&self, x: i32, y: i32) -> Relation {
+        self.locate_exact(i128::from(x), i128::from(y))
+    }
+
+    pub fn locate_exact<T: Exact + Default>(&self, x: T, y: T) -> Relation {
+        self.ring_exact(x, y)
+            .map_or(Relation::Border, Ring::relation)
+    }
+
+    // `None` for a point that `axes = "border"` puts on the border.
+    pub fn ring_exact<T: Exact + Default>(&self, x: T, y: T) -> Option<Ring> {
+        self.resolve(x, y, |band| self.band_ring(band, x, y))
+            .map(|(_, _, ring)| ring)
+    }
+
+    pub fn band_ring<T: Exact>(&self, band: &Band, x: T, y: T) -> Ring {
+        ring(self.border_count(), |k| {
+            band.shape(k).relation(x, y, T::from(self.border(k)))
+        })
+    }
+
+    pub fn band_ring_f64(&self, band: &Band, x: f64, y: f64, eps: f64) -> Ring {
+        ring(self.border_count(), |k| {
+            band.shape(k).relation_f64(x, y, self.border(k).into(), eps)
+        })
+    }
+
+    pub fn locate_f64(&self, x: f64, y: f64, eps: f64) -> Relation {
+        self.resolve(x, y, |band| self.band_ring_f64(band, x, y, eps))
+            .map_or(Relation::Border, |(_, _, ring)| ring.relation())
+    }
+
+    // The quadrants or the sector whose bands classify `(x, y)`; none when the
+    // point is on an axis that `axes = "border"` makes border.
+    pub fn parts<T: Coordinate>(&self, x: T, y: T) -> Vec<(Part, &Band)> {
+        let Some(quadrants) = &self.quadrants else {
+            let k = sector::select(&self.sectors, x, y);
+            return vec![(Part::Sector(k), &self.sectors[k].band)];
+        };
+        let zero = T::default();
+        let chosen = match self.axes {
+            _ if x != zero && y != zero => vec![Quadrant::of(x, y)],
+            Axes::Sign => vec![Quadrant::of(x, y)],
+            Axes::Quadrant(quadrant) => vec![quadrant],
+            Axes::Border => vec![],
+            Axes::Union => QUADRANTS
+                .into_iter()
+                .filter(|quadrant| quadrant.touches(x, y))
+                .collect(),
+        };
+        chosen
+            .into_iter()
+            .map(|quadrant| (Part::Quadrant(quadrant), quadrants.band(quadrant)))
+            .collect()
+    }
+
+    // Rings `(x, y)` in each of its parts and keeps the first of the strongest:
+    // inside, then border, then outside, as `csg::union` combines them.
+    pub fn resolve<T: Coordinate>(
+        &self,
+        x: T,
+        y: T,
+        mut ring_of: impl FnMut(&Band) -> Ring,
+    ) -> Option<(Part, &Band, Ring)> {
+        let strength = |ring: Ring| match ring.relation() {
+            Relation::Inside => 2,
+            Relation::Border => 1,
+            Relation::Outside => 0,
+        };
+        let mut best: Option<(Part, &Band, Ring)> = None;
+        for (part, band) in self.parts(x, y) {
+            let ring = ring_of(band);
+            if best.is_none_or(|(_, _, best)| strength(ring) > strength(best)) {
+                best = Some((part, band, ring));
+            }
+        }
+        best
+    }
+
+    pub fn bands(&self) -> Vec<&Band> {
+        match &self.quadrants {
+            Some(quadrants) => quadrants.bands().to_vec(),
+            None => self.sectors.iter().map(|sector| &sector.band).collect(),
+        }
+    }
+
+    pub fn border_count(&self) -> usize {
+        2 + self.extra_borders.len()
+    }
+
+    pub fn border(&self, k: usize) -> i32 {
+        match k {
+            0 => self.border_inner,
+            1 => self.border_outer,
+            _ => self.extra_borders[k - 2],
+        }
+    }
+
+    pub fn borders(&self) -> impl Iterator<Item = i32> + '_ {
+        (0..self.border_count()).map(|k| self.border(k))
+    }
+
+    pub fn max_border(&self) -> i32 {
+        self.borders().max().unwrap_or_default()
+    }
+
+    pub fn shapes(&self) -> impl Iterator<Item = &Shape> {
+        self.bands().into_iter().flat_map(Band::shapes)
+    }
+
+    // Whether every shape, and the choice of sector, stays exact for
+    // coordinates and borders up to `reach` in absolute value.
+    pub fn fits(&self, reach: i128) -> bool {
+        self.shapes().all(|shape| shape.fits(reach))
+            && sector::magnitude(&self.sectors, reach).is_some_and(|m| m <= MAX_MAGNITUDE)
+    }
+
+    // Whether `locate_exact` stays exact for the coordinate `value` of a
+    // point whose other coordinate is `other`: `Ratio` arithmetic forms the
+    // same products as the lattice geometry would with the point and the
+    // borders scaled by both denominators.
+    pub fn ratio_fits(&self, value: Ratio, other: Ratio) -> bool {
+        let border = i128::from(self.max_border().max(1));
+        let reach = || {
+            let num = value.num().abs().checked_mul(other.den())?;
+            let border = border.checked_mul(value.den())?.checked_mul(other.den())?;
+            Some(num.max(border))
+        };
+        reach().is_some_and(|reach| self.fits(reach))
+    }
+}
+
+// `[[figure]]` entries split the plane into quadrant bands, `[[composite]]`
+// entries combine shapes into one `csg::Region` and `[[formula]]` entries are
+// written as inequalities (see `expr`).
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FigureFile {
+    #[serde(default)]
+    pub figure: Vec<FigureSpec>,
+    #[serde(default)]
+    pub composite: Vec<Composite>,
+    #[serde(default)]
+    pub formula: Vec<Formula>,
+}
+
+pub fn parse_toml(text: &str) -> Result<FigureFile, String> {
+    let file: FigureFile = toml::from_str(text).map_err(|e| e.to_string())?;
+    check(file)
+}
+
+pub fn parse_json(text: &str) -> Result<FigureFile, String> {
+    let file: FigureFile = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    check(file)
+}
+
+pub fn load(path: &Path) -> Result<FigureFile, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let figures = match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => parse_json(&text),
+        _ => parse_toml(&text),
+    };
+    figures.map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn check(file: FigureFile) -> Result<FigureFile, String> {
+    let mut names = HashSet::new();
+    let all = file.figure.iter().map(|f| &f.name);
+    let all = all.chain(file.composite.iter().map(|c| &c.name));
+    for name in all.chain(file.formula.iter().map(|f| &f.name)) {
+        if !names.insert(name.as_str()) {
+            return Err(format!("duplicate figure name `{}`", name));
+        }
+    }
+    for figure in &file.figure {
+        if figure.borders().any(|border| border < 0) {
+            return Err(format!(
+                "figure `{}`: borders must not be negative",
+                figure.name
+            ));
+        }
+        let borders: Vec<i32> = figure.borders().collect();
+        if borders.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(format!(
+                "figure `{}`: borders must strictly increase from `border_inner` through `extra_borders`",
+                figure.name
+            ));
+        }
+        if figure.quadrants.is_some() != figure.sectors.is_empty() {
+            return Err(format!(
+                "figure `{}`: give either `quadrants` or `sectors`",
+                figure.name
+            ));
+        }
+        if !figure.sectors.is_empty() && !figure.axes.is_sign() {
+            return Err(format!(
+                "figure `{}`: `axes` only applies to `quadrants`",
+                figure.name
+            ));
+        }
+        sector::check(&figure.sectors).map_err(|e| format!("figure `{}`: {}", figure.name, e))?;
+        let extra = figure.extra_borders.len();
+        if figure.bands().iter().any(|band| band.extra.len() != extra) {
+            return Err(format!(
+                "figure `{}`: every quadrant or sector needs one `extra` shape per extra border",
+                figure.name
+            ));
+        }
+        check_range(&figure.name, figure.range)?;
+        check_shapes(figure)?;
+    }
+    for composite in &file.composite {
+        check_range(&composite.name, composite.range)?;
+        composite
+            .region
+            .check(reach(composite.range))
+            .map_err(|e| format!("figure `{}`: {}", composite.name, e))?;
+    }
+    for formula in &file.formula {
+        check_range(&formula.name, formula.range)?;
+        if !formula.expression.fits(reach(formula.range)) {
+            return Err(format!(
+                "figure `{}`: expression overflows exact arithmetic over its range",
+                formula.name
+            ));
+        }
+    }
+    Ok(file)
+}
+
+fn check_range(name: &str, range: Range) -> Result<(), String> {
+    for (axis, bounds) in [("x", range.x), ("y", range.y)] {
+        if bounds.lo() > bounds.hi() {
+            return Err(format!("figure `{}`: empty {} range", name, axis));
+        }
+    }
+    Ok(())
+}
+
+// The largest absolute coordinate in `range`.
+fn reach(range: Range) -> i128 {
+    [range.x.lo(), range.x.hi(), range.y.lo(), range.y.hi()]
+        .into_iter()
+        .map(|c| i128::from(c).abs())
+        .max()
+        .unwrap_or_default()
+}
+
+fn check_shapes(figure: &FigureSpec) -> Result<(), String> {
+    for shape in figure.shapes() {
+        shape
+            .check()
+            .map_err(|e| format!("figure `{}`: {}", figure.name, e))?;
+    }
+    let reach = reach(figure.range).max(figure.max_border().into());
+    if !figure.fits(reach) {
+        return Err(format!(
+            "figure `{}`: shapes overflow exact arithmetic over its range",
+            figure.name
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,433 @@
+use crate::figure::Relation;
+use crate::grid::MAX_GRID_CELLS;
+use crate::ratio::Ratio;
+use crate::render::png_grid;
+use crate::sector;
+use crate::spec::{Band, FigureSpec, Part, Quadrant, Shape};
+use serde_derive::Serialize;
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+
+// Directions per part for the area integral, and pieces per edge or per
+// curve for the border length.
+const RAYS: usize = 1 << 14;
+const EDGE_PIECES: usize = 1 << 10;
+const CURVE_PIECES: usize = 1 << 14;
+
+// `rational + pi * π`, the form every area and border length built from
+// boxes and circles takes.
+#[derive(Serialize, Clone, Copy)]
+pub struct Closed {
+    pub rational: Ratio,
+    pub pi: Ratio,
+}
+
+impl Closed {
+    fn new(rational: Ratio, pi: Ratio) -> Self {
+        Closed { rational, pi }
+    }
+
+    fn zero() -> Self {
+        Closed::new(Ratio::from(0), Ratio::from(0))
+    }
+
+    fn plus(self, other: Closed) -> Self {
+        Closed::new(self.rational + other.rational, self.pi + other.pi)
+    }
+
+    fn minus(self, other: Closed) -> Self {
+        Closed::new(self.rational + -other.rational, self.pi + -other.pi)
+    }
+
+    fn times(self, k: Ratio) -> Self {
+        Closed::new(self.rational * k, self.pi * k)
+    }
+
+    fn value(self) -> f64 {
+        let real = |r: Ratio| r.num() as f64 / r.den() as f64;
+        real(self.rational) + real(self.pi) * PI
+    }
+}
+
+// `exact` is present when the value has a closed form (see `Closed`).
+#[derive(Serialize)]
+pub struct Measure {
+    pub value: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exact: Option<Closed>,
+}
+
+impl Measure {
+    fn new(exact: Option<Closed>, approximate: impl FnOnce() -> f64) -> Self {
+        Measure {
+            value: exact.map_or_else(approximate, Closed::value),
+            exact,
+        }
+    }
+}
+
+#[derive(Serialize, Default)]
+pub struct Counts {
+    pub inside: u64,
+    pub border: u64,
+    pub outside: u64,
+}
+
+// `area` is the area classified inside and is absent when that is unbounded,
+// i.e. with an odd number of borders. `border_length` is the length of the
+// shapes' borders classified border; axes that `axes = "border"` adds are not
+// in it. `lattice` counts the points of the figure's range per relation, and
+// is absent when the range has more than `MAX_GRID_CELLS` points.
+#[derive(Serialize)]
+pub struct Stats {
+    pub figure: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub area: Option<Measure>,
+    pub border_length: Measure,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lattice: Option<Counts>,
+}
+
+// What a shape contributes to one quadrant, as multiples of its border:
+// `area` of `border^2`, `length` of `border`, and `radii` the least and
+// greatest squared distance from the centre to its border, of `border^2`.
+// `key` tells shapes that are the same up to their border apart.
+struct Quarter {
+    key: (u8, [i32; 2]),
+    area: Closed,
+    length: Option<Closed>,
+    radii: (Ratio, Ratio),
+}
+
+// Shapes symmetric under turns by 90° have the same quarter in every
+// quadrant; `None` for those without a closed-form area.
+fn quarter(shape: &Shape) -> Option<Quarter> {
+    let (zero, one, two) = (Ratio::from(0), Ratio::from(1), Ratio::from(2));
+    let half = Ratio::new(1, 2);
+    Some(match shape {
+        Shape::Box | Shape::RotatedBox([_, 0]) | Shape::RotatedBox([0, _]) => Quarter {
+            key: (0, [0, 0]),
+            area: Closed::new(one, zero),
+            length: Some(Closed::new(two, zero)),
+            radii: (one, two),
+        },
+        Shape::Circle | Shape::Lp(2) => Quarter {
+            key: (1, [0, 0]),
+            area: Closed::new(zero, Ratio::new(1, 4)),
+            length: Some(Closed::new(zero, half)),
+            radii: (one, one),
+        },
+        // the diamond's sides and the ellipse's arcs have no closed-form length
+        Shape::Diamond | Shape::Lp(1) => Quarter {
+            key: (2, [0, 0]),
+            area: Closed::new(half, zero),
+            length: None,
+            radii: (half, one),
+        },
+        Shape::Ellipse(axes) => {
+            let (a, b) = (i128::from(axes[0]), i128::from(axes[1]));
+            Quarter {
+                key: (3, *axes),
+                area: Closed::new(zero, Ratio::new(a * b, 4)),
+                length: None,
+                radii: (Ratio::from(a.min(b).pow(2)), Ratio::from(a.max(b).pow(2))),
+            }
+        }
+        Shape::RotatedBox(side) => Quarter {
+            key: (4, *side),
+            area: Closed::new(one, zero),
+            length: Some(Closed::new(two, zero)),
+            radii: (one, two),
+        },
+        Shape::Lp(_) | Shape::Polygon(_) => return None,
+    })
+}
+
+// The exact area inside and border length in one quadrant, when every shape
+// has a closed form and each lies within the next: then ring `k` is shape `k`
+// less shape `k - 1`, and every border is border all along. Two shapes are
+// known to nest when they are the same one with growing borders, or when the
+// farthest point of the inner one is nearer than the nearest of the outer.
+fn exact_band(spec: &FigureSpec, band: &Band) -> Option<(Closed, Option<Closed>)> {
+    let quarters: Vec<(Quarter, i128)> = (0..spec.border_count())
+        .map(|k| Some((quarter(band.shape(k))?, i128::from(spec.border(k)))))
+        .collect::<Option<_>>()?;
+    let mut area = Closed::zero();
+    let mut length = Some(Closed::zero());
+    for (k, (quarter, border)) in quarters.iter().enumerate() {
+        let square = Ratio::from(border * border);
+        let same = match k.checked_sub(1).map(|j| &quarters[j]) {
+            None => false,
+            Some((inner, inner_border)) => {
+                let same = inner.key == quarter.key;
+                let nested = if same {
+                    inner_border <= border
+                } else {
+                    inner.radii.1 * Ratio::from(inner_border * inner_border)
+                        < quarter.radii.0 * square
+                };
+                if !nested {
+                    return None;
+                }
+                if k % 2 == 1 {
+                    let inner_area = inner.area.times(Ratio::from(inner_border * inner_border));
+                    area = area.plus(quarter.area.times(square)).minus(inner_area);
+                }
+                same && inner_border == border
+            }
+        };
+        if !same {
+            length = length
+                .zip(quarter.length)
+                .map(|(total, l)| total.plus(l.times(Ratio::from(*border))));
+        }
+    }
+    Some((area, length))
+}
+
+// The ranges of `rho` along the ray from the centre in direction `(c, s)`
+// that are inside `shape` with border `border`.
+pub fn radial(shape: &Shape, border: f64, (c, s): (f64, f64)) -> Vec<(f64, f64)> {
+    let gauge = match shape {
+        Shape::Box => c.abs().max(s.abs()),
+        Shape::Circle => 1.0,
+        Shape::Diamond => c.abs() + s.abs(),
+        Shape::Lp(p) => {
+            let p = f64::from(*p);
+            (c.abs().powf(p) + s.abs().powf(p)).powf(p.recip())
+        }
+        Shape::Ellipse([a, b]) => (c / f64::from(*a)).hypot(s / f64::from(*b)),
+        Shape::RotatedBox([rc, rs]) => {
+            let (rc, rs) = (f64::from(*rc), f64::from(*rs));
+            let n = rc.hypot(rs);
+            ((rc * c + rs * s) / n)
+                .abs()
+                .max(((rc * s - rs * c) / n).abs())
+        }
+        // even-odd: the ray leaves and enters at each edge it crosses
+        Shape::Polygon(polygon) => {
+            let points = polygon.points(border);
+            let mut hits: Vec<f64> = (0..points.len())
+                .filter_map(|i| {
+                    let (p, q) = (points[i], points[(i + 1) % points.len()]);
+                    let (ex, ey) = (q.0 - p.0, q.1 - p.1);
+                    let den = c * ey - s * ex;
+                    if den == 0.0 {
+                        return None;
+                    }
+                    let rho = (p.0 * ey - p.1 * ex) / den;
+                    let along = (p.0 * s - p.1 * c) / den;
+                    (rho > 0.0 && (0.0..1.0).contains(&along)).then_some(rho)
+                })
+                .collect();
+            hits.sort_by(f64::total_cmp);
+            if hits.len() % 2 == 1 {
+                hits.insert(0, 0.0);
+            }
+            return hits.chunks(2).map(|pair| (pair[0], pair[1])).collect();
+        }
+    };
+    if border > 0.0 {
+        vec![(0.0, border / gauge)]
+    } else {
+        vec![]
+    }
+}
+
+// `∫ rho drho` over the pieces of the ray in odd rings, up to the outermost
+// border crossing.
+fn ray_area(spec: &FigureSpec, band: &Band, direction: (f64, f64)) -> f64 {
+    let inside: Vec<Vec<(f64, f64)>> = (0..spec.border_count())
+        .map(|k| radial(band.shape(k), spec.border(k).into(), direction))
+        .collect();
+    let mut stops: Vec<f64> = inside.iter().flatten().flat_map(|&(a, b)| [a, b]).collect();
+    stops.push(0.0);
+    stops.sort_by(f64::total_cmp);
+    stops.dedup();
+    stops
+        .windows(2)
+        .filter(|pair| {
+            let mid = (pair[0] + pair[1]) / 2.0;
+            let holds = |ranges: &Vec<(f64, f64)>| ranges.iter().any(|&(a, b)| a < mid && mid < b);
+            inside.iter().position(holds).is_some_and(|k| k % 2 == 1)
+        })
+        .map(|pair| (pair[1] * pair[1] - pair[0] * pair[0]) / 2.0)
+        .sum()
+}
+
+// Where each part of the layout starts and ends, as angles.
+pub fn parts(spec: &FigureSpec) -> Vec<(Part, &Band, (f64, f64))> {
+    if let Some(quadrants) = &spec.quadrants {
+        return [
+            (Quadrant::UpperRight, 0.0),
+            (Quadrant::UpperLeft, FRAC_PI_2),
+            (Quadrant::LowerLeft, PI),
+            (Quadrant::LowerRight, -FRAC_PI_2),
+        ]
+        .into_iter()
+        .map(|(quadrant, from)| {
+            let band = quadrants.band(quadrant);
+            (Part::Quadrant(quadrant), band, (from, from + FRAC_PI_2))
+        })
+        .collect();
+    }
+    let sectors = &spec.sectors;
+    (0..sectors.len())
+        .map(|k| {
+            let from = sectors[k].from.degrees().to_radians();
+            let mut to = sectors[(k + 1) % sectors.len()].from.degrees().to_radians();
+            if to <= from {
+                to += TAU;
+            }
+            (Part::Sector(k), &sectors[k].band, (from, to))
+        })
+        .collect()
+}
+
+pub fn in_part(spec: &FigureSpec, part: Part, (x, y): (f64, f64)) -> bool {
+    match part {
+        Part::Quadrant(quadrant) => Quadrant::of(x, y) == quadrant,
+        Part::Sector(k) => sector::select(&spec.sectors, x, y) == k,
+    }
+}
+
+// The closed polyline along the border of `shape`, with a vertex wherever an
+// axis crosses a box, a diamond or a curve.
+fn outline(shape: &Shape, border: f64) -> Vec<(f64, f64)> {
+    let edges = |corners: Vec<(f64, f64)>| -> Vec<(f64, f64)> {
+        (0..corners.len())
+            .flat_map(|i| {
+                let (p, q) = (corners[i], corners[(i + 1) % corners.len()]);
+                (0..EDGE_PIECES).map(move |j| {
+                    let t = j as f64 / EDGE_PIECES as f64;
+                    (p.0 + t * (q.0 - p.0), p.1 + t * (q.1 - p.1))
+                })
+            })
+            .collect()
+    };
+    let curve = |point: &dyn Fn(f64, f64) -> (f64, f64)| -> Vec<(f64, f64)> {
+        (0..CURVE_PIECES)
+            .map(|j| {
+                let (s, c) = (TAU * j as f64 / CURVE_PIECES as f64).sin_cos();
+                point(c, s)
+            })
+            .collect()
+    };
+    let b = border;
+    match shape {
+        Shape::Box => edges(vec![(b, b), (-b, b), (-b, -b), (b, -b)]),
+        Shape::Diamond => edges(vec![(b, 0.0), (0.0, b), (-b, 0.0), (0.0, -b)]),
+        Shape::RotatedBox([c, s]) => {
+            let (c, s) = (f64::from(*c), f64::from(*s));
+            let n = c.hypot(s);
+            let (u, v) = ((b * c / n, b * s / n), (-b * s / n, b * c / n));
+            edges(vec![
+                (u.0 + v.0, u.1 + v.1),
+                (-u.0 + v.0, -u.1 + v.1),
+                (-u.0 - v.0, -u.1 - v.1),
+                (u.0 - v.0, u.1 - v.1),
+            ])
+        }
+        Shape::Polygon(polygon) => edges(polygon.points(b)),
+        Shape::Circle => curve(&|c, s| (b * c, b * s)),
+        Shape::Ellipse([a, e]) => curve(&|c, s| (b * f64::from(*a) * c, b * f64::from(*e) * s)),
+        Shape::Lp(p) => {
+            let e = 2.0 / f64::from(*p);
+            curve(&|c, s| {
+                (
+                    b * c.abs().powf(e).copysign(c),
+                    b * s.abs().powf(e).copysign(s),
+                )
+            })
+        }
+    }
+}
+
+// Sums the pieces of each border that lie in the band's part and outside
+// every border before it, judged at their midpoints.
+fn approximate_length(spec: &FigureSpec) -> f64 {
+    let eps = 1e-9 * f64::from(spec.max_border().max(1));
+    let mut length = 0.0;
+    for (part, band, _) in parts(spec) {
+        for k in 0..spec.border_count() {
+            let points = outline(band.shape(k), spec.border(k).into());
+            for i in 0..points.len() {
+                let (p, q) = (points[i], points[(i + 1) % points.len()]);
+                let mid = ((p.0 + q.0) / 2.0, (p.1 + q.1) / 2.0);
+                let shown = (0..k).all(|j| {
+                    let relation =
+                        band.shape(j)
+                            .relation_f64(mid.0, mid.1, spec.border(j).into(), eps);
+                    matches!(relation, Relation::Outside)
+                });
+                if shown && in_part(spec, part, mid) {
+                    length += (q.0 - p.0).hypot(q.1 - p.1);
+                }
+            }
+        }
+    }
+    length
+}
+
+// The midpoint rule over `RAYS` directions in each part.
+fn approximate_area(spec: &FigureSpec) -> f64 {
+    parts(spec)
+        .into_iter()
+        .map(|(_, band, (from, to))| {
+            let step = (to - from) / RAYS as f64;
+            let sum: f64 = (0..RAYS)
+                .map(|i| {
+                    let (s, c) = (from + (i as f64 + 0.5) * step).sin_cos();
+                    ray_area(spec, band, (c, s))
+                })
+                .sum();
+            sum * step
+        })
+        .sum()
+}
+
+fn lattice(spec: &FigureSpec) -> Option<Counts> {
+    let grid = png_grid(&spec.range);
+    if grid.cells() > MAX_GRID_CELLS {
+        return None;
+    }
+    let mut counts = Counts::default();
+    for relation in grid.classify(spec).into_iter().flatten() {
+        *match relation {
+            Relation::Inside => &mut counts.inside,
+            Relation::Border => &mut counts.border,
+            Relation::Outside => &mut counts.outside,
+        } += 1;
+    }
+    Some(counts)
+}
+
+// Exact for quadrant figures whose quadrants all have a closed form (see
+// `exact_band`), approximated numerically otherwise.
+pub fn stats(spec: &FigureSpec) -> Stats {
+    let exact: Option<Vec<_>> = spec.quadrants.as_ref().and_then(|quadrants| {
+        quadrants
+            .bands()
+            .into_iter()
+            .map(|band| exact_band(spec, band))
+            .collect()
+    });
+    let (area, length) = match exact {
+        Some(bands) => {
+            let area = bands
+                .iter()
+                .fold(Closed::zero(), |sum, (area, _)| sum.plus(*area));
+            let length = bands.iter().try_fold(Closed::zero(), |sum, (_, length)| {
+                Some(sum.plus((*length)?))
+            });
+            (Some(area), length)
+        }
+        None => (None, None),
+    };
+    let bounded = spec.border_count().is_multiple_of(2);
+    Stats {
+        figure: spec.name.clone(),
+        area: bounded.then(|| Measure::new(area, || approximate_area(spec))),
+        border_length: Measure::new(length, || approximate_length(spec)),
+        lattice: lattice(spec),
+    }
+}
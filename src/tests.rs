@@ -1,14 +1,62 @@
 use super::*;
+use proptest::prelude::*;
+use ratio::Ratio;
 use warp::http::StatusCode;
 use warp::test::request;
 
+fn figures() -> Vec<spec::FigureSpec> {
+    spec::parse_toml(include_str!("../figures.toml"))
+        .unwrap()
+        .figure
+}
+
+fn registry() -> Arc<Registry> {
+    Arc::new(Registry::from(figures()))
+}
+
+#[test]
+fn figures_toml_matches_point_location() {
+    let figures = figures();
+    for x in -99..100 {
+        for y in -99..100 {
+            assert_eq!(figures[0].locate(x, y), figure::point_location1(x, y));
+            assert_eq!(figures[1].locate(x, y), figure::point_location2(x, y));
+        }
+    }
+}
+
+#[test]
+fn figure_spec_rejects_duplicate_names() {
+    let text = include_str!("../figures.toml");
+    let doubled = format!("{}\n{}", text, text);
+    assert!(spec::parse_toml(&doubled).is_err());
+}
+
+#[test]
+fn figure_spec_loads_json() {
+    let json = r#"{"figure": [{
+        "name": "figure-3",
+        "border_inner": 5,
+        "border_outer": 7,
+        "quadrants": {
+            "upper_right": {"inner": "box", "outer": "box"},
+            "lower_right": {"inner": "box", "outer": "box"},
+            "upper_left": {"inner": "circle", "outer": "circle"},
+            "lower_left": {"inner": "circle", "outer": "circle"}
+        }
+    }]}"#;
+    let figures = spec::parse_json(json).unwrap().figure;
+    assert_eq!(figures[0].locate(6, 6), figure::Relation::Inside);
+    assert_eq!(figures[0].locate(-5, 0), figure::Relation::Border);
+}
+
 //Figure-1 tests
 #[tokio::test]
 async fn figure1_test_get_border() {
     let resp = request()
         .method("GET")
         .path("/figure-1?x=10&y=10")
-        .reply(&routes())
+        .reply(&routes(registry()))
         .await;
 
     assert_eq!(resp.status(), StatusCode::OK);
@@ -19,7 +67,7 @@ async fn figure1_test_get_outside() {
     let resp = request()
         .method("GET")
         .path("/figure-1?x=5&y=5")
-        .reply(&routes())
+        .reply(&routes(registry()))
         .await;
 
     assert_eq!(resp.status(), StatusCode::OK);
@@ -30,7 +78,7 @@ async fn figure1_test_get_outside1() {
     let resp = request()
         .method("GET")
         .path("/figure-1?x=20&y=15")
-        .reply(&routes())
+        .reply(&routes(registry()))
         .await;
 
     assert_eq!(resp.status(), StatusCode::OK);
@@ -41,7 +89,7 @@ async fn figure1_test_get_inside() {
     let resp = request()
         .method("GET")
         .path("/figure-1?x=10&y=15")
-        .reply(&routes())
+        .reply(&routes(registry()))
         .await;
 
     assert_eq!(resp.status(), StatusCode::OK);
@@ -52,7 +100,7 @@ async fn figure1_test_too_much_coord_error() {
     let resp = request()
         .method("GET")
         .path("/figure-1?x=10&y=10&z=23")
-        .reply(&routes())
+        .reply(&routes(registry()))
         .await;
 
     assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
@@ -62,7 +110,7 @@ async fn figure1_test_variable_not_defined_error() {
     let resp = request()
         .method("GET")
         .path("/figure-1?")
-        .reply(&routes())
+        .reply(&routes(registry()))
         .await;
 
     assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
@@ -74,7 +122,7 @@ async fn figure2_test_get_border() {
     let resp = request()
         .method("GET")
         .path("/figure-2?x=20&y=20")
-        .reply(&routes())
+        .reply(&routes(registry()))
         .await;
 
     assert_eq!(resp.status(), StatusCode::OK);
@@ -85,7 +133,7 @@ async fn figure2_test_get_outside() {
     let resp = request()
         .method("GET")
         .path("/figure-2?x=11&y=10")
-        .reply(&routes())
+        .reply(&routes(registry()))
         .await;
 
     assert_eq!(resp.status(), StatusCode::OK);
@@ -96,7 +144,7 @@ async fn figure2_test_get_outside1() {
     let resp = request()
         .method("GET")
         .path("/figure-2?x=43&y=41")
-        .reply(&routes())
+        .reply(&routes(registry()))
         .await;
 
     assert_eq!(resp.status(), StatusCode::OK);
@@ -107,7 +155,7 @@ async fn figure2_test_get_inside() {
     let resp = request()
         .method("GET")
         .path("/figure-2?x=-25&y=15")
-        .reply(&routes())
+        .reply(&routes(registry()))
         .await;
 
     assert_eq!(resp.status(), StatusCode::OK);
@@ -118,7 +166,7 @@ async fn figure2_test_too_much_coord_error() {
     let resp = request()
         .method("GET")
         .path("/figure-2?x=10&y=10&z=23")
-        .reply(&routes())
+        .reply(&routes(registry()))
         .await;
 
     assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
@@ -128,8 +176,2347 @@ async fn figure2_test_variable_not_defined_error() {
     let resp = request()
         .method("GET")
         .path("/figure-2?")
-        .reply(&routes())
+        .reply(&routes(registry()))
+        .await;
+
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+//Registry tests
+#[tokio::test]
+async fn figure_route_by_name() {
+    let resp = request()
+        .method("GET")
+        .path("/figure/figure-2?x=-25&y=15")
+        .reply(&routes(registry()))
+        .await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body(), "inside");
+}
+#[tokio::test]
+async fn figure_route_unknown_name() {
+    let resp = request()
+        .method("GET")
+        .path("/figure/figure-9?x=1&y=1")
+        .reply(&routes(registry()))
+        .await;
+
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(body["error"], "unknown_figure");
+    assert_eq!(body["figure"], "figure-9");
+    assert_eq!(body["known"], serde_json::json!(["figure-1", "figure-2"]));
+}
+#[tokio::test]
+async fn figure_route_bad_query() {
+    let resp = request()
+        .method("GET")
+        .path("/figure/figure-1?x=100&y=1")
+        .reply(&routes(registry()))
+        .await;
+
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+#[tokio::test]
+async fn figures_listing() {
+    let resp = request()
+        .method("GET")
+        .path("/figures")
+        .reply(&routes(registry()))
+        .await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(body[0]["name"], "figure-1");
+    assert_eq!(body[1]["border_outer"], 40);
+    assert_eq!(body[1]["quadrants"]["lower_right"]["outer"], "box");
+}
+
+//Error response tests
+async fn error_body(path: &str) -> serde_json::Value {
+    let resp = request()
+        .method("GET")
+        .path(path)
+        .reply(&routes(registry()))
+        .await;
+
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    serde_json::from_slice(resp.body()).unwrap()
+}
+#[tokio::test]
+async fn error_out_of_range() {
+    let body = error_body("/figure-1?x=10&y=-100").await;
+    assert_eq!(body["error"], "out_of_range");
+    assert_eq!(body["field"], "y");
+    assert_eq!(body["range"], serde_json::json!({"min": -99, "max": 99}));
+}
+#[tokio::test]
+async fn error_unknown_field() {
+    let body = error_body("/figure/figure-2?x=10&y=10&z=23").await;
+    assert_eq!(body["error"], "unknown_field");
+    assert_eq!(body["field"], "z");
+}
+#[tokio::test]
+async fn error_missing_field() {
+    let body = error_body("/figure-1?x=3").await;
+    assert_eq!(body["error"], "missing_field");
+    assert_eq!(body["field"], "y");
+    assert_eq!(body["range"], serde_json::json!({"min": -99, "max": 99}));
+}
+#[tokio::test]
+async fn error_not_a_number() {
+    let body = error_body("/figure-1?x=ten&y=3").await;
+    assert_eq!(body["error"], "not_a_number");
+    assert_eq!(body["field"], "x");
+}
+#[tokio::test]
+async fn error_duplicate_field() {
+    let body = error_body("/figure-1?x=1&y=1&x=2").await;
+    assert_eq!(body["error"], "invalid_value");
+    assert_eq!(body["field"], "x");
+    let body = error_body("/figure-1/grid?x0=0&x1=1&y0=0&y1=1&step=1&step=2").await;
+    assert_eq!(body["field"], "step");
+}
+
+//Batch tests
+#[tokio::test]
+async fn batch_classifies_in_order() {
+    let resp = request()
+        .method("POST")
+        .path("/figure-1/batch")
+        .json(&serde_json::json!([
+            {"x": 10, "y": 10},
+            {"x": 5, "y": 5},
+            {"x": 10, "y": 15}
+        ]))
+        .reply(&routes(registry()))
+        .await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(body, serde_json::json!(["border", "outside", "inside"]));
+}
+#[tokio::test]
+async fn batch_reports_item_errors() {
+    let resp = request()
+        .method("POST")
+        .path("/figure/figure-2/batch")
+        .json(&serde_json::json!([
+            {"x": 20, "y": 20},
+            {"x": 200, "y": 20},
+            {"x": 1.5, "y": 20},
+            {"x": 1},
+            {"x": -25, "y": 15}
+        ]))
+        .reply(&routes(registry()))
+        .await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(body[0], "border");
+    assert_eq!(body[1]["error"], "out_of_range");
+    assert_eq!(body[1]["field"], "x");
+    assert_eq!(body[2]["error"], "not_a_number");
+    assert_eq!(body[3]["error"], "missing_field");
+    assert_eq!(body[4], "inside");
+}
+#[tokio::test]
+async fn batch_rejects_malformed_body() {
+    let resp = request()
+        .method("POST")
+        .path("/figure-1/batch")
+        .body("{not json")
+        .reply(&routes(registry()))
+        .await;
+
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+//Grid tests
+#[tokio::test]
+async fn grid_text_matches_point_location() {
+    let resp = request()
+        .method("GET")
+        .path("/figure-1/grid?x0=-20&x1=20&y0=-20&y1=20&step=5")
+        .reply(&routes(registry()))
+        .await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    let text = std::str::from_utf8(resp.body()).unwrap();
+    let rows: Vec<&str> = text.lines().collect();
+    assert_eq!(rows.len(), 9);
+    for (i, row) in rows.iter().enumerate() {
+        let y = 20 - 5 * i as i32;
+        let expected: String = (0..9)
+            .map(|j| figure::point_location1(-20 + 5 * j, y).symbol())
+            .collect();
+        assert_eq!(*row, expected);
+    }
+}
+#[tokio::test]
+async fn grid_json_rows() {
+    let resp = request()
+        .method("GET")
+        .path("/figure/figure-2/grid?x0=25&x1=20&y0=20&y1=20&step=5&format=json")
+        .reply(&routes(registry()))
+        .await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(body, serde_json::json!([["border", "inside"]]));
+}
+#[tokio::test]
+async fn grid_rejects_bad_step() {
+    let body = error_body("/figure-1/grid?x0=0&x1=1&y0=0&y1=1&step=0").await;
+    assert_eq!(body["error"], "out_of_range");
+    assert_eq!(body["field"], "step");
+    assert_eq!(body["range"]["min"], 1);
+}
+
+//Render tests
+#[tokio::test]
+async fn render_svg() {
+    let resp = request()
+        .method("GET")
+        .path("/figure-1/render.svg")
+        .reply(&routes(registry()))
+        .await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], "image/svg+xml");
+    let svg = std::str::from_utf8(resp.body()).unwrap();
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.ends_with("</svg>"));
+    assert!(svg.contains("<title>figure-1</title>"));
+    // upper right: box of 10 masked out of a circle of 20
+    assert!(svg.contains(r#"<mask id="mask-upper-right"><path d="M20,0 A20,20 0 1,0 -20,0 A20,20 0 1,0 20,0 Z" fill="white"/><path d="M-10,-10 H10 V10 H-10 Z" fill="black"/></mask>"#));
+    assert_eq!(svg.matches("clip-path=").count(), 4);
+}
+#[tokio::test]
+async fn render_png() {
+    let resp = request()
+        .method("GET")
+        .path("/figure-2/render.png?scale=2&border=%23ff0000&inside=00ff00")
+        .reply(&routes(registry()))
         .await;
 
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], "image/png");
+    let decoder = png::Decoder::new(resp.body().as_ref());
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    assert_eq!((info.width, info.height), (398, 398));
+
+    let pixel = |x: i32, y: i32| {
+        let column = (x - figure::COORD_MIN) as usize * 2;
+        let row = (figure::COORD_MAX - y) as usize * 2;
+        let offset = (row * info.width as usize + column) * 3;
+        &pixels[offset..offset + 3]
+    };
+    assert_eq!(pixel(20, 20), [0xff, 0, 0]);
+    assert_eq!(pixel(-25, 15), [0, 0xff, 0]);
+    assert_eq!(pixel(43, 41), [0xff, 0xff, 0xff]);
+}
+#[tokio::test]
+async fn render_png_rejects_bad_colour() {
+    let body = error_body("/figure-2/render.png?outside=blue").await;
+    assert_eq!(body["error"], "invalid_value");
+    assert_eq!(body["field"], "outside");
+}
+#[tokio::test]
+async fn render_ascii() {
+    let resp = request()
+        .method("GET")
+        .path("/figure-1/ascii?scale=5")
+        .reply(&routes(registry()))
+        .await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    let text = std::str::from_utf8(resp.body()).unwrap();
+    let rows: Vec<&str> = text.lines().collect();
+    // -95..=95 in steps of 5, y = 15 is the fourth row above the x axis
+    assert_eq!(rows.len(), 39);
+    assert!(rows.iter().all(|row| row.chars().count() == 39));
+    assert!(rows[19].starts_with('-'));
+    assert_eq!(rows[0].chars().nth(19), Some('|'));
+    assert_eq!(rows[16].chars().nth(21), Some('#'));
+    assert_eq!(rows[17].chars().nth(21), Some('+'));
+}
+#[test]
+fn ascii_step_fits_columns() {
+    let range = figure::Range::default();
+    assert_eq!(render::ascii_step(&range, 200), 1);
+    assert_eq!(render::ascii_step(&range, 80), 3);
+    assert_eq!(render::ascii_step(&range, 40), 5);
+
+    let wide = figure::Range {
+        x: figure::Bounds::new(-30_000, 10_000),
+        y: figure::Bounds::default(),
+    };
+    assert_eq!(render::ascii_step(&wide, 80), 501);
+
+    let tall = figure::Range {
+        x: figure::Bounds::new(-10, 10),
+        y: figure::Bounds::new(-400_000_000, 400_000_000),
+    };
+    let step = render::ascii_step(&tall, 80);
+    assert!(render::ascii_grid(&tall, step).cells() <= grid::MAX_GRID_CELLS);
+    assert!(render::ascii_grid(&tall, step - 1).cells() > grid::MAX_GRID_CELLS);
+}
+
+#[tokio::test]
+async fn ascii_limits_cells() {
+    let text = r#"
+[[figure]]
+name = "tall"
+border_inner = 10
+border_outer = 20
+
+[figure.range]
+x = { min = -10, max = 10 }
+y = { min = -400000000, max = 400000000 }
+
+[figure.quadrants]
+upper_right = { inner = "box", outer = "circle" }
+lower_right = { inner = "circle", outer = "circle" }
+upper_left = { inner = "circle", outer = "box" }
+lower_left = { inner = "box", outer = "box" }
+"#;
+    let registry = Arc::new(Registry::from(spec::parse_toml(text).unwrap()));
+    let resp = request()
+        .path("/tall/ascii")
+        .reply(&routes(registry.clone()))
+        .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = std::str::from_utf8(resp.body()).unwrap();
+    let cells: usize = body.lines().map(|line| line.chars().count()).sum();
+    assert!(cells > 0 && cells as u64 <= grid::MAX_GRID_CELLS);
+
+    let resp = request()
+        .path("/tall/ascii?scale=1")
+        .reply(&routes(registry))
+        .await;
     assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(
+        (body["error"].as_str(), body["field"].as_str()),
+        (Some("too_many_cells"), Some("scale"))
+    );
+}
+
+//Explain tests
+#[tokio::test]
+async fn explain_trace() {
+    let resp = request()
+        .method("GET")
+        .path("/figure-1?x=20&y=15&explain=true")
+        .reply(&routes(registry()))
+        .await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "figure": "figure-1",
+            "x": 20,
+            "y": 15,
+            "quadrant": "upper_right",
+            "inner": {
+                "shape": "box",
+                "function": "box_calc",
+                "border": 10,
+                "distance": 20,
+                "compared_to": 10,
+                "relation": "outside"
+            },
+            "outer": {
+                "shape": "circle",
+                "function": "radii_calc",
+                "border": 20,
+                "distance": 625,
+                "compared_to": 400,
+                "relation": "outside"
+            },
+            "arm": "outer_outside",
+            "ring": 2,
+            "relation": "outside"
+        })
+    );
+}
+#[test]
+fn explain_agrees_with_locate() {
+    for spec in figures() {
+        for x in -99..100 {
+            for y in -99..100 {
+                let explanation = explain::explain(&spec, x, y);
+                assert_eq!(explanation.relation, spec.locate(x, y));
+                assert_eq!(
+                    explanation.outer.is_none(),
+                    explanation.arm.starts_with("inner")
+                );
+            }
+        }
+    }
+}
+
+//Content negotiation tests
+async fn negotiated(accept: &str) -> warp::http::Response<warp::hyper::body::Bytes> {
+    request()
+        .method("GET")
+        .path("/figure-1?x=10&y=15")
+        .header("accept", accept)
+        .reply(&routes(registry()))
+        .await
+}
+#[tokio::test]
+async fn negotiate_json() {
+    let resp = negotiated("application/json").await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], "application/json");
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({"x": 10, "y": 15, "relation": "inside", "figure": "figure-1", "ring": 1})
+    );
+}
+#[tokio::test]
+async fn negotiate_csv() {
+    let resp = negotiated("text/csv").await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.body(),
+        "x,y,relation,figure,ring\n10,15,inside,figure-1,1\n"
+    );
+}
+#[tokio::test]
+async fn negotiate_binary() {
+    let resp = negotiated("application/cbor").await;
+    let body: serde_json::Value = ciborium::from_reader(resp.body().as_ref()).unwrap();
+    assert_eq!(body["relation"], "inside");
+
+    let resp = negotiated("application/msgpack").await;
+    let body: serde_json::Value = rmp_serde::from_slice(resp.body()).unwrap();
+    assert_eq!(body["figure"], "figure-1");
+}
+#[tokio::test]
+async fn negotiate_reports_the_ring() {
+    let registry = Arc::new(Registry::from(
+        spec::parse_toml(&format!("{}{}", TARGET, CSG_FIGURE_1)).unwrap(),
+    ));
+    let get = |path: &'static str, accept: &'static str| {
+        let registry = registry.clone();
+        async move {
+            request()
+                .path(path)
+                .header("accept", accept)
+                .reply(&routes(registry))
+                .await
+        }
+    };
+    let resp = get("/target?x=-35&y=0", "application/json").await;
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(
+        (&body["relation"], &body["ring"]),
+        (&"inside".into(), &3.into())
+    );
+    let resp = get("/target?x=18&y=24", "application/json").await;
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(body["border"], 2);
+    assert!(body.get("ring").is_none());
+
+    let resp = get("/target?x=-35&y=0", "text/csv").await;
+    assert_eq!(
+        resp.body(),
+        "x,y,relation,figure,ring\n-35,0,inside,target,3\n"
+    );
+    let resp = get("/target?x=18&y=24", "text/csv").await;
+    assert_eq!(
+        resp.body(),
+        "x,y,relation,figure,border\n18,24,border,target,2\n"
+    );
+    let resp = get("/target?x=-50&y=50", "application/cbor").await;
+    let body: serde_json::Value = ciborium::from_reader(resp.body().as_ref()).unwrap();
+    assert_eq!(body["ring"], 4);
+    let resp = get("/target?x=-50&y=50", "application/msgpack").await;
+    let body: serde_json::Value = rmp_serde::from_slice(resp.body()).unwrap();
+    assert_eq!(body["ring"], 4);
+
+    // composites have no rings
+    let resp = get("/figure-1-csg?x=15&y=5", "application/json").await;
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert!(body.get("ring").is_none() && body.get("border").is_none());
+    let resp = get("/figure-1-csg?x=15&y=5", "text/csv").await;
+    assert_eq!(
+        resp.body(),
+        "x,y,relation,figure\n15,5,inside,figure-1-csg\n"
+    );
+}
+#[tokio::test]
+async fn negotiate_prefers_highest_quality() {
+    let resp = negotiated("text/csv;q=0.5, application/json;q=0.9, */*;q=0.1").await;
+    assert_eq!(resp.headers()["content-type"], "application/json");
+
+    let resp = negotiated("text/*").await;
+    assert_eq!(resp.body(), "inside");
+}
+#[tokio::test]
+async fn negotiate_not_acceptable() {
+    let resp = negotiated("image/png, application/json;q=0").await;
+    assert_eq!(resp.status(), StatusCode::NOT_ACCEPTABLE);
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(body["error"], "not_acceptable");
+}
+
+//Coordinate range tests
+const RANGED: &str = r#"
+[[figure]]
+name = "figure-3"
+border_inner = 10
+border_outer = 20
+
+[figure.range]
+x = { min = -10, max = 500 }
+y = { min = -30000, max = 5 }
+
+[figure.quadrants]
+upper_right = { inner = "box", outer = "circle" }
+lower_right = { inner = "circle", outer = "circle" }
+upper_left = { inner = "circle", outer = "box" }
+lower_left = { inner = "box", outer = "box" }
+"#;
+
+fn ranged() -> Arc<Registry> {
+    Arc::new(Registry::from(spec::parse_toml(RANGED).unwrap()))
+}
+
+#[tokio::test]
+async fn range_is_enforced_per_figure() {
+    let resp = request()
+        .method("GET")
+        .path("/figure-3?x=300&y=-20000")
+        .reply(&routes(ranged()))
+        .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body(), "outside");
+
+    let resp = request()
+        .method("GET")
+        .path("/figure-3?x=-11&y=0")
+        .reply(&routes(ranged()))
+        .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(body["error"], "out_of_range");
+    assert_eq!(body["range"], serde_json::json!({"min": -10, "max": 500}));
+
+    let resp = request()
+        .method("GET")
+        .path("/figure-3?x=0&y=6")
+        .reply(&routes(ranged()))
+        .await;
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(body["field"], "y");
+    assert_eq!(body["range"], serde_json::json!({"min": -30000, "max": 5}));
+}
+#[tokio::test]
+async fn range_in_metadata() {
+    let resp = request()
+        .method("GET")
+        .path("/figures")
+        .reply(&routes(registry()))
+        .await;
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(
+        body[0]["range"],
+        serde_json::json!({"x": {"min": -99, "max": 99}, "y": {"min": -99, "max": 99}})
+    );
+}
+#[tokio::test]
+async fn range_limits_grid_and_png() {
+    let resp = request()
+        .method("GET")
+        .path("/figure-3/grid?x0=-10&x1=500&y0=-500&y1=5")
+        .reply(&routes(ranged()))
+        .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(body["error"], "too_many_cells");
+
+    let resp = request()
+        .method("GET")
+        .path("/figure-3/render.png")
+        .reply(&routes(ranged()))
+        .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+#[test]
+fn range_rejects_empty_bounds() {
+    let empty = RANGED.replace("max = 500", "max = -20");
+    assert!(spec::parse_toml(&empty).is_err());
+}
+
+//Wide arithmetic tests
+const UNBOUNDED: &str = r#"
+[[figure]]
+name = "huge"
+border_inner = 2147483646
+border_outer = 2147483647
+
+[figure.range]
+x = {}
+y = {}
+
+[figure.quadrants]
+upper_right = { inner = "box", outer = "circle" }
+lower_right = { inner = "circle", outer = "circle" }
+upper_left = { inner = "circle", outer = "box" }
+lower_left = { inner = "box", outer = "box" }
+"#;
+
+#[test]
+fn geometry_is_exact_at_the_extremes() {
+    use figure::{box_calc, radii_calc, Relation::*};
+
+    let (min, max) = (i128::from(i32::MIN), i128::from(i32::MAX));
+    assert_eq!(box_calc(min, 0, max), Outside);
+    assert_eq!(box_calc(-max, min + 1, max), Border);
+    assert_eq!(radii_calc(min, min, max), Outside);
+    assert_eq!(radii_calc(max, 0, max), Border);
+    assert_eq!(radii_calc(max - 1, 1, max), Inside);
+    // a scaled 3-4-5 triangle lands exactly on the circle
+    assert_eq!(
+        radii_calc(1_200_000_000i128, -1_600_000_000, 2_000_000_000),
+        Border
+    );
+    assert_eq!(
+        radii_calc(1_200_000_000i128, -1_600_000_001, 2_000_000_000),
+        Outside
+    );
+    assert_eq!(
+        radii_calc(1_200_000_000i128, -1_599_999_999, 2_000_000_000),
+        Inside
+    );
+}
+#[tokio::test]
+async fn unbounded_range() {
+    let registry = Arc::new(Registry::from(spec::parse_toml(UNBOUNDED).unwrap()));
+    let resp = request()
+        .method("GET")
+        .path("/huge?x=2147483647&y=-2147483648")
+        .reply(&routes(registry.clone()))
+        .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body(), "outside");
+
+    let resp = request()
+        .method("GET")
+        .path("/huge?x=2147483646&y=-1000")
+        .reply(&routes(registry.clone()))
+        .await;
+    assert_eq!(resp.body(), "inside");
+
+    let resp = request()
+        .method("GET")
+        .path("/huge?x=2147483648&y=0")
+        .reply(&routes(registry.clone()))
+        .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(body["error"], "out_of_range");
+    assert_eq!(body["range"], serde_json::json!({"min": null, "max": null}));
+}
+#[tokio::test]
+async fn unbounded_renders_and_grids() {
+    let registry = Arc::new(Registry::from(spec::parse_toml(UNBOUNDED).unwrap()));
+    for path in [
+        "/huge/render.png",
+        "/huge/grid?x0=-2147483648&x1=2147483647&y0=-2147483648&y1=2147483647",
+    ] {
+        let resp = request()
+            .method("GET")
+            .path(path)
+            .reply(&routes(registry.clone()))
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{path}");
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["error"], "too_many_cells", "{path}");
+    }
+
+    let resp = request()
+        .method("GET")
+        .path("/huge/render.svg")
+        .reply(&routes(registry))
+        .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let svg = String::from_utf8(resp.body().to_vec()).unwrap();
+    assert!(svg.contains(r#"width="6442450938""#));
+}
+
+//Real-valued coordinate tests
+async fn real(path: &str) -> (StatusCode, String) {
+    let resp = request()
+        .method("GET")
+        .path(path)
+        .reply(&routes(registry()))
+        .await;
+    let body = String::from_utf8(resp.body().to_vec()).unwrap();
+    (resp.status(), body)
+}
+#[tokio::test]
+async fn real_exact_border() {
+    let (status, body) = real("/figure-1/real?x=10.0&y=10.0&exact=true").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "border");
+    assert_eq!(
+        real("/figure-1/real?x=-10.50&y=-3&exact=true").await.1,
+        "inside"
+    );
+    assert_eq!(
+        real("/figure-1/real?x=12&y=16.000000001&exact=true")
+            .await
+            .1,
+        "outside"
+    );
+}
+#[tokio::test]
+async fn real_float_with_eps() {
+    assert_eq!(real("/figure-1/real?x=10.0&y=10.0").await.1, "border");
+    assert_eq!(real("/figure-1/real?x=12.0000001&y=16").await.1, "outside");
+    assert_eq!(
+        real("/figure-1/real?x=12.0000001&y=16&eps=0.001").await.1,
+        "border"
+    );
+    assert_eq!(real("/figure-2/real?x=-25.5&y=15.25").await.1, "inside");
+}
+#[tokio::test]
+async fn real_errors() {
+    let (status, body) = real("/figure-1/real?x=1.5&y=2&exact=true&eps=0.1").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("invalid_value"));
+    assert!(real("/figure-1/real?x=1e400&y=2")
+        .await
+        .1
+        .contains("not_a_number"));
+    assert!(real("/figure-1/real?x=99.5&y=2")
+        .await
+        .1
+        .contains("out_of_range"));
+    assert!(real("/figure-1/real?x=99.5&y=2&exact=1")
+        .await
+        .1
+        .contains("out_of_range"));
+    assert!(real("/figure-1/real?x=12&y=16.000000000000000001&exact=1")
+        .await
+        .1
+        .contains("invalid_value"));
+    assert!(real("/figure-1/real?x=1..5&y=2&exact=1")
+        .await
+        .1
+        .contains("not_a_number"));
+}
+#[test]
+fn scaled_points_match_lattice() {
+    for spec in figures() {
+        for x in (-99..100).step_by(3) {
+            for y in (-99..100).step_by(3) {
+                let expected = spec.locate(x, y);
+                let (rx, ry) = (i128::from(x) * 1000, i128::from(y) * 1000);
+                let (rx, ry) = (Ratio::new(rx, 1000), Ratio::new(ry, 1000));
+                assert_eq!(spec.locate_exact(rx, ry), expected);
+                assert_eq!(spec.locate_f64(x.into(), y.into(), 0.0), expected);
+            }
+        }
+    }
+}
+
+//Rational coordinate tests
+#[tokio::test]
+async fn real_fractions() {
+    assert_eq!(
+        real("/figure-1/real?x=20/2&y=10&exact=true").await.1,
+        "border"
+    );
+    assert_eq!(
+        real("/figure-1/real?x=36/3&y=-16&exact=1").await.1,
+        "border"
+    );
+    assert_eq!(
+        real("/figure-1/real?x=15/2&y=-5&exact=1").await.1,
+        "outside"
+    );
+    assert_eq!(
+        real("/figure-1/real?x=-21/2&y=-3&exact=1").await.1,
+        "inside"
+    );
+    assert_eq!(
+        real("/figure-1/real?x=-1/-3&y=1&exact=1").await.1,
+        "outside"
+    );
+    assert!(real("/figure-1/real?x=1/0&y=1&exact=1")
+        .await
+        .1
+        .contains("invalid_value"));
+    assert!(real("/figure-1/real?x=1.5/2&y=1&exact=1")
+        .await
+        .1
+        .contains("not_a_number"));
+    assert!(real("/figure-1/real?x=199/2&y=1&exact=1")
+        .await
+        .1
+        .contains("out_of_range"));
+}
+
+const EXACT: &str = r#"
+[[figure]]
+name = "exact"
+border_inner = 10
+border_outer = 20
+arithmetic = "exact"
+
+[figure.quadrants]
+upper_right = { inner = "box", outer = "circle" }
+lower_right = { inner = "circle", outer = "circle" }
+upper_left = { inner = "circle", outer = "box" }
+lower_left = { inner = "box", outer = "box" }
+"#;
+
+#[tokio::test]
+async fn figure_arithmetic_setting() {
+    let registry = Arc::new(Registry::from(spec::parse_toml(EXACT).unwrap()));
+    let get = |path: &'static str| {
+        let registry = registry.clone();
+        async move {
+            let resp = request().path(path).reply(&routes(registry)).await;
+            String::from_utf8(resp.body().to_vec()).unwrap()
+        }
+    };
+    assert_eq!(get("/exact/real?x=12&y=16.000000001").await, "outside");
+    assert_eq!(get("/exact/real?x=24/2&y=16").await, "border");
+    // an explicit `exact=false` or an `eps` goes back to floating point
+    assert!(get("/exact/real?x=24/2&y=16&exact=false")
+        .await
+        .contains("not_a_number"));
+    assert_eq!(
+        get("/exact/real?x=12&y=16.000000001&eps=0.001").await,
+        "border"
+    );
+}
+
+#[test]
+fn ratio_is_kept_in_lowest_terms() {
+    assert_eq!(Ratio::new(-15, -6), Ratio::new(5, 2));
+    assert_eq!(Ratio::new(3, -9).num(), -1);
+    assert_eq!(Ratio::new(3, -9).den(), 3);
+    assert_eq!(Ratio::new(0, -7), Ratio::default());
+    assert!(Ratio::new(1, 3) < Ratio::new(34, 100));
+}
+
+proptest! {
+    #[test]
+    fn rational_geometry_matches_integers(x in any::<i32>(), y in any::<i32>(), b in 0..=i32::MAX) {
+        let (rx, ry, rb) = (Ratio::from(x), Ratio::from(y), Ratio::from(b));
+        let (ix, iy, ib) = (i128::from(x), i128::from(y), i128::from(b));
+        prop_assert_eq!(figure::box_calc(rx, ry, rb), figure::box_calc(ix, iy, ib));
+        prop_assert_eq!(figure::radii_calc(rx, ry, rb), figure::radii_calc(ix, iy, ib));
+    }
+
+    #[test]
+    fn rational_figures_match_integers(
+        x in -150..150,
+        y in -150..150,
+        k in 1i128..1_000_000,
+    ) {
+        for spec in figures() {
+            let (rx, ry) = (Ratio::from(x), Ratio::from(y));
+            prop_assert_eq!(spec.locate_exact(rx, ry), spec.locate(x, y));
+            let (rx, ry) = (Ratio::new(i128::from(x) * k, k), Ratio::new(i128::from(y) * k, k));
+            prop_assert_eq!(spec.locate_exact(rx, ry), spec.locate(x, y));
+        }
+    }
+}
+
+//Distance metric tests
+const SHAPES: &str = r#"
+[[figure]]
+name = "shapes"
+border_inner = 10
+border_outer = 20
+
+[figure.quadrants]
+upper_right = { inner = "diamond", outer = { lp = 4 } }
+lower_right = { inner = { ellipse = [2, 1] }, outer = "circle" }
+upper_left = { inner = { rotated_box = [1, 1] }, outer = "box" }
+lower_left = { inner = "box", outer = { rotated_box = [3, 4] } }
+"#;
+
+#[test]
+fn metric_primitives() {
+    use figure::{diamond_calc, ellipse_calc, lp_calc, rotated_box_calc, Relation::*};
+
+    assert_eq!(diamond_calc(3i128, -7, 10), Border);
+    assert_eq!(diamond_calc(-5i128, 6, 10), Outside);
+    assert_eq!(lp_calc(10i128, 0, 10, 4), Border);
+    assert_eq!(lp_calc(8i128, -8, 10, 4), Inside);
+    assert_eq!(lp_calc(9i128, 8, 10, 4), Outside);
+    assert_eq!(ellipse_calc(20i128, 0, 10, (2, 1)), Border);
+    assert_eq!(ellipse_calc(12i128, 8, 10, (2, 1)), Border);
+    assert_eq!(ellipse_calc(0i128, 11, 10, (2, 1)), Outside);
+    assert_eq!(rotated_box_calc(5i128, 2, 5, (1, 1)), Inside);
+    assert_eq!(rotated_box_calc(6i128, 2, 5, (1, 1)), Outside);
+    // the side vector (3, 4) has length 5, and (-1, 7) is 5 along it and 5 across
+    assert_eq!(rotated_box_calc(-1i128, 7, 5, (3, 4)), Border);
+    assert_eq!(
+        rotated_box_calc(Ratio::new(3, 5), Ratio::new(4, 5), Ratio::from(1), (3, 4)),
+        Border
+    );
+}
+
+proptest! {
+    #[test]
+    fn metrics_reduce_to_box_and_circle(
+        x in any::<i32>(),
+        y in any::<i32>(),
+        b in 0..=i32::MAX,
+        k in 1..1000,
+    ) {
+        use spec::Shape::*;
+
+        let (x, y, b) = (i128::from(x), i128::from(y), i128::from(b));
+        let (circle, boxed) = (Circle.relation(x, y, b), Box.relation(x, y, b));
+        prop_assert_eq!(Lp(1).relation(x, y, b), Diamond.relation(x, y, b));
+        prop_assert_eq!(&Lp(2).relation(x, y, b), &circle);
+        let (kx, ky) = (x * i128::from(k), y * i128::from(k));
+        prop_assert_eq!(&Ellipse([k, k]).relation(kx, ky, b), &circle);
+        prop_assert_eq!(&RotatedBox([k, 0]).relation(x, y, b), &boxed);
+        prop_assert_eq!(&RotatedBox([0, -k]).relation(x, y, b), &boxed);
+    }
+}
+
+#[test]
+fn figure_spec_checks_shapes() {
+    let with = |shape: &str, range: &str| {
+        SHAPES.replace("{ lp = 4 }", shape).replace(
+            "[figure.quadrants]",
+            &format!("{}\n[figure.quadrants]", range),
+        )
+    };
+    assert!(spec::parse_toml(SHAPES).is_ok());
+    for shape in [
+        "{ lp = 0 }",
+        "{ ellipse = [0, 1] }",
+        "{ rotated_box = [0, 0] }",
+    ] {
+        assert!(spec::parse_toml(&with(shape, "")).is_err(), "{}", shape);
+    }
+    let unbounded = "[figure.range]\nx = {}\ny = {}\n";
+    assert!(spec::parse_toml(&with("{ lp = 4 }", unbounded)).is_ok());
+    let err = spec::parse_toml(&with("{ lp = 5 }", unbounded))
+        .err()
+        .unwrap();
+    assert!(err.contains("overflow"), "{}", err);
+}
+
+#[tokio::test]
+async fn metric_figure_routes() {
+    let registry = Arc::new(Registry::from(spec::parse_toml(SHAPES).unwrap()));
+    let get = |path: &'static str| {
+        let registry = registry.clone();
+        async move {
+            let resp = request().path(path).reply(&routes(registry)).await;
+            String::from_utf8(resp.body().to_vec()).unwrap()
+        }
+    };
+    assert_eq!(get("/shapes?x=4&y=6").await, "border");
+    assert_eq!(get("/shapes?x=12&y=12").await, "inside");
+    assert_eq!(get("/shapes?x=12&y=-8").await, "border");
+    assert_eq!(get("/shapes?x=-15&y=20").await, "border");
+    assert_eq!(get("/shapes/real?x=24/2&y=-8&exact=1").await, "border");
+    assert_eq!(get("/shapes/real?x=12.001&y=-8&eps=0.01").await, "border");
+
+    let body: serde_json::Value =
+        serde_json::from_str(&get("/shapes?x=12&y=12&explain=true").await).unwrap();
+    assert_eq!(body["outer"]["shape"], serde_json::json!({"lp": 4}));
+    assert_eq!(body["outer"]["function"], "lp_calc");
+    assert_eq!(body["outer"]["distance"], 2 * 12i64.pow(4));
+
+    let svg = get("/shapes/render.svg").await;
+    assert!(svg.contains("M20,0 A20,10 0 1,0 -20,0 A20,10 0 1,0 20,0 Z"));
+    assert!(svg.contains("M10,0 L0,10 L-10,0 L0,-10 Z"));
+}
+
+//Polygon tests
+#[test]
+fn polygon_calc_standalone() {
+    use figure::Relation::*;
+    use polygon::polygon_calc;
+
+    let square: Vec<(i128, i128)> = vec![(0, 0), (4, 0), (4, 4), (0, 4)];
+    assert_eq!(polygon_calc(2, 2, &square), Inside);
+    assert_eq!(polygon_calc(4, 2, &square), Border);
+    assert_eq!(polygon_calc(0, 0, &square), Border);
+    assert_eq!(polygon_calc(2, 4, &square), Border);
+    assert_eq!(polygon_calc(5, 2, &square), Outside);
+    assert_eq!(polygon_calc(2, 5, &square), Outside);
+
+    // the horizontal through (-1, 2) passes exactly through the vertex (4, 2)
+    let triangle: Vec<(i128, i128)> = vec![(0, 0), (4, 2), (0, 4)];
+    assert_eq!(polygon_calc(-1, 2, &triangle), Outside);
+    assert_eq!(polygon_calc(5, 2, &triangle), Outside);
+    assert_eq!(polygon_calc(1, 2, &triangle), Inside);
+    assert_eq!(polygon_calc(2, 1, &triangle), Border);
+    assert_eq!(polygon_calc(2, 3, &triangle), Border);
+
+    // concave: an L with the notch at the top right
+    let l: Vec<(i128, i128)> = vec![(0, 0), (4, 0), (4, 2), (2, 2), (2, 4), (0, 4)];
+    assert_eq!(polygon_calc(3, 3, &l), Outside);
+    assert_eq!(polygon_calc(1, 3, &l), Inside);
+    assert_eq!(polygon_calc(3, 2, &l), Border);
+    assert_eq!(polygon_calc(2, 2, &l), Border);
+
+    let half = Ratio::new(1, 2);
+    let thin = [
+        (Ratio::from(0), Ratio::from(0)),
+        (Ratio::from(1), half),
+        (Ratio::from(0), Ratio::from(1)),
+    ];
+    assert_eq!(polygon_calc(half, Ratio::new(1, 4), &thin), Border);
+    assert_eq!(polygon_calc(Ratio::new(1, 3), half, &thin), Inside);
+    assert_eq!(
+        polygon_calc(Ratio::new(1, 2), Ratio::new(1, 5), &thin),
+        Outside
+    );
+}
+
+fn polygon_shape(vertices: &str) -> spec::Shape {
+    serde_json::from_str(&format!(r#"{{"polygon": {}}}"#, vertices)).unwrap()
+}
+
+proptest! {
+    #[test]
+    fn polygons_match_box_and_diamond(x in any::<i32>(), y in any::<i32>(), b in 0..=i32::MAX) {
+        let (x, y, b) = (i128::from(x), i128::from(y), i128::from(b));
+        let square = polygon_shape("[[1, 1], [-1, 1], [-1, -1], [1, -1]]");
+        let diamond = polygon_shape(r#"[["1/2", 0], [0, "1/2"], ["-1/2", 0], [0, "-0.5"]]"#);
+        prop_assert_eq!(square.relation(x, y, b), spec::Shape::Box.relation(x, y, b));
+        prop_assert_eq!(
+            diamond.relation(x, y, 2 * b),
+            spec::Shape::Diamond.relation(x, y, b)
+        );
+    }
+}
+
+const POLYGON: &str = r#"
+[[figure]]
+name = "polygon"
+border_inner = 10
+border_outer = 20
+
+[figure.quadrants]
+upper_right = { inner = "box", outer = { polygon = [[0, 0], [1, 0], ["1/2", 1]] } }
+lower_right = { inner = "circle", outer = "circle" }
+upper_left = { inner = "circle", outer = "box" }
+lower_left = { inner = "box", outer = "box" }
+"#;
+
+#[test]
+fn figure_spec_checks_polygons() {
+    assert!(spec::parse_toml(POLYGON).is_ok());
+    let short = POLYGON.replace(r#"[[0, 0], [1, 0], ["1/2", 1]]"#, "[[0, 0], [1, 0]]");
+    let err = spec::parse_toml(&short).err().unwrap();
+    assert!(err.contains("at least 3 vertices"), "{}", err);
+    let zero = POLYGON.replace(r#""1/2""#, r#""1/0""#);
+    let err = spec::parse_toml(&zero).err().unwrap();
+    assert!(err.contains("invalid number `1/0`"), "{}", err);
+}
+
+#[tokio::test]
+async fn polygon_figure_routes() {
+    let registry = Arc::new(Registry::from(spec::parse_toml(POLYGON).unwrap()));
+    let get = |path: &'static str| {
+        let registry = registry.clone();
+        async move {
+            let resp = request().path(path).reply(&routes(registry)).await;
+            String::from_utf8(resp.body().to_vec()).unwrap()
+        }
+    };
+    // the triangle (0, 0), (20, 0), (10, 20) minus the inner box
+    assert_eq!(get("/polygon?x=12&y=12").await, "inside");
+    assert_eq!(get("/polygon?x=15&y=10").await, "border");
+    assert_eq!(get("/polygon?x=16&y=9").await, "outside");
+    assert_eq!(get("/polygon?x=10&y=20").await, "border");
+    assert_eq!(get("/polygon/real?x=31/2&y=9&exact=1").await, "border");
+    assert_eq!(get("/polygon/real?x=15.5&y=9.001&eps=0.01").await, "border");
+    assert_eq!(get("/polygon/real?x=15.5&y=9.1&eps=0.01").await, "outside");
+
+    let body: serde_json::Value =
+        serde_json::from_str(&get("/polygon?x=12&y=12&explain=true").await).unwrap();
+    let outer = &body["outer"];
+    assert_eq!(
+        outer["shape"],
+        serde_json::json!({"polygon": [[0, 0], [1, 0], ["1/2", 1]]})
+    );
+    assert_eq!(outer["function"], "polygon_calc");
+    assert_eq!(outer["relation"], "inside");
+    assert!(outer.get("distance").is_none());
+
+    assert!(get("/polygon/render.svg")
+        .await
+        .contains("M0.000,0.000 L20.000,0.000 L10.000,20.000 Z"));
+}
+
+//CSG tests
+#[test]
+fn csg_operations_propagate_border() {
+    use csg::{complement, difference, intersection, union};
+    use figure::Relation::*;
+
+    assert_eq!(union(Border, Inside), Inside);
+    assert_eq!(union(Border, Outside), Border);
+    assert_eq!(union(Outside, Outside), Outside);
+    assert_eq!(intersection(Border, Inside), Border);
+    assert_eq!(intersection(Border, Outside), Outside);
+    assert_eq!(intersection(Inside, Inside), Inside);
+    assert_eq!(complement(Inside), Outside);
+    assert_eq!(complement(Border), Border);
+    assert_eq!(difference(Inside, Border), Border);
+    assert_eq!(difference(Border, Inside), Outside);
+    assert_eq!(difference(Inside, Outside), Inside);
+
+    // (10, 0) is on the border of the small circle and inside the big box
+    let region: csg::Region = serde_json::from_str(
+        r#"{"union": [{"shape": "circle", "border": 10}, {"shape": "box", "border": 15}]}"#,
+    )
+    .unwrap();
+    assert_eq!(region.relation(10i128, 0), Inside);
+    assert_eq!(region.relation(15i128, 3), Border);
+    assert_eq!(region.relation(Ratio::new(31, 2), Ratio::from(0)), Outside);
+}
+
+const CSG_FIGURE_1: &str = r#"
+[[composite]]
+name = "figure-1-csg"
+
+[composite.region]
+union = [
+    { intersection = [{ quadrant = "upper_right" }, { difference = [{ shape = "circle", border = 20 }, { shape = "box", border = 10 }] }] },
+    { intersection = [{ quadrant = "lower_right" }, { difference = [{ shape = "circle", border = 20 }, { shape = "circle", border = 10 }] }] },
+    { intersection = [{ quadrant = "upper_left" }, { difference = [{ shape = "box", border = 20 }, { shape = "circle", border = 10 }] }] },
+    { intersection = [{ quadrant = "lower_left" }, { difference = [{ shape = "box", border = 20 }, { shape = "box", border = 10 }] }] },
+]
+"#;
+
+// three quadrants share a band, so take the odd one out of theirs
+const CSG_FIGURE_2: &str = r#"
+[[composite]]
+name = "figure-2-csg"
+
+[composite.region]
+union = [
+    { intersection = [{ quadrant = "upper_right" }, { difference = [{ shape = "circle", border = 40 }, { shape = "box", border = 20 }] }] },
+    { difference = [{ shape = "box", border = 40 }, { shape = "circle", border = 20 }, { quadrant = "upper_right" }] },
+]
+"#;
+
+fn lab_csg() -> String {
+    format!("{}{}", CSG_FIGURE_1, CSG_FIGURE_2)
+}
+
+#[test]
+fn csg_reproduces_lab_figures() {
+    let registry = Registry::from(spec::parse_toml(&lab_csg()).unwrap());
+    let figures = figures();
+    let (csg_1, csg_2) = (
+        registry.get("figure-1-csg").unwrap(),
+        registry.get("figure-2-csg").unwrap(),
+    );
+    for x in -99..100 {
+        for y in -99..100 {
+            assert_eq!(
+                csg_1.locate(x, y),
+                figures[0].locate(x, y),
+                "({}, {})",
+                x,
+                y
+            );
+            assert_eq!(
+                csg_2.locate(x, y),
+                figures[1].locate(x, y),
+                "({}, {})",
+                x,
+                y
+            );
+        }
+    }
+}
+
+#[test]
+fn figure_file_checks_composites() {
+    let composite = |region: &str| {
+        format!(
+            "[[composite]]\nname = \"c\"\n\n[composite.region]\n{}\n",
+            region
+        )
+    };
+    assert!(spec::parse_toml(&composite("shape = \"box\"\nborder = 3")).is_ok());
+    for (region, message) in [
+        ("union = []", "need a region"),
+        ("shape = \"box\"\nborder = -3", "must not be negative"),
+        ("shape = { lp = 0 }\nborder = 3", "p >= 1"),
+        (
+            "complement = { shape = \"box\" }",
+            "did not match any variant",
+        ),
+    ] {
+        let err = spec::parse_toml(&composite(region)).err().unwrap();
+        assert!(err.contains(message), "{}: {}", region, err);
+    }
+    let clash = format!(
+        "{}\n{}",
+        include_str!("../figures.toml"),
+        composite("shape = \"box\"\nborder = 3").replace("\"c\"", "\"figure-1\"")
+    );
+    let err = spec::parse_toml(&clash).err().unwrap();
+    assert!(err.contains("duplicate figure name `figure-1`"), "{}", err);
+}
+
+#[tokio::test]
+async fn composite_routes() {
+    let registry = Arc::new(Registry::from(spec::parse_toml(&lab_csg()).unwrap()));
+    let get = |path: &'static str| {
+        let registry = registry.clone();
+        async move {
+            let resp = request().path(path).reply(&routes(registry)).await;
+            (
+                resp.status(),
+                String::from_utf8(resp.body().to_vec()).unwrap(),
+            )
+        }
+    };
+    assert_eq!(get("/figure-1-csg?x=15&y=5").await.1, "inside");
+    assert_eq!(get("/figure/figure-2-csg?x=-40&y=7").await.1, "border");
+    for path in [
+        "/figure-1-csg?x=1&y=1&explain=true",
+        "/figure-1-csg/real?x=1&y=1",
+        "/figure-1-csg/distance?x=1&y=1",
+        "/figure/figure-1-csg/nearest?x=1&y=1",
+        "/figure-1-csg/stats",
+        "/figure-1-csg/render.svg",
+    ] {
+        let (status, body) = get(path).await;
+        assert_eq!(status, StatusCode::NOT_IMPLEMENTED, "{}", path);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({"error": "unsupported", "figure": "figure-1-csg"})
+        );
+    }
+    let resp = request()
+        .method("POST")
+        .path("/figure-1-csg/path")
+        .json(&serde_json::json!([[0, 0], [1, 1]]))
+        .reply(&routes(registry.clone()))
+        .await;
+    assert_eq!(resp.status(), StatusCode::NOT_IMPLEMENTED);
+
+    let body: serde_json::Value = serde_json::from_str(&get("/figures").await.1).unwrap();
+    assert_eq!(body[0]["name"], "figure-1-csg");
+    assert_eq!(
+        body[0]["region"]["union"][0]["intersection"][0],
+        serde_json::json!({"quadrant": "upper_right"})
+    );
+    assert_eq!(
+        body[0]["region"]["union"][0]["intersection"][1]["difference"][1],
+        serde_json::json!({"shape": "box", "border": 10})
+    );
+}
+
+const TARGET: &str = r#"
+[[figure]]
+name = "target"
+border_inner = 10
+border_outer = 20
+extra_borders = [30, 40]
+
+[figure.quadrants]
+upper_right = { inner = "circle", outer = "circle", extra = ["circle", "circle"] }
+lower_right = { inner = "box", outer = "box", extra = ["box", "box"] }
+upper_left = { inner = "circle", outer = "box", extra = ["circle", "box"] }
+lower_left = { inner = "diamond", outer = "diamond", extra = ["diamond", "diamond"] }
+"#;
+
+#[test]
+fn rings_alternate_from_the_innermost() {
+    use figure::{Relation::*, Ring};
+    let target = &spec::parse_toml(TARGET).unwrap().figure[0];
+    assert_eq!(target.border_count(), 4);
+    for (x, y, ring, relation) in [
+        (0, 0, Ring::Ring(0), Outside),
+        (10, 0, Ring::Border(0), Border),
+        (15, 0, Ring::Ring(1), Inside),
+        (12, -20, Ring::Border(1), Border),
+        (25, -25, Ring::Ring(2), Outside),
+        (18, 24, Ring::Border(2), Border),
+        (-35, 0, Ring::Ring(3), Inside),
+        (-20, -20, Ring::Border(3), Border),
+        (-50, 50, Ring::Ring(4), Outside),
+    ] {
+        assert_eq!(target.ring_exact(i128::from(x), i128::from(y)), Some(ring));
+        assert_eq!(target.locate(x, y), relation, "({}, {})", x, y);
+    }
+}
+
+#[test]
+fn two_borders_are_a_single_ring() {
+    use figure::Ring;
+    let figures = figures();
+    for x in -99..100 {
+        for y in -99..100 {
+            let (x, y) = (i128::from(x), i128::from(y));
+            let ring = figures[0].ring_exact(x, y).unwrap();
+            assert!(matches!(ring, Ring::Border(0..=1) | Ring::Ring(0..=2)));
+            assert_eq!(ring.relation(), figures[0].locate_exact(x, y));
+        }
+    }
+}
+
+#[test]
+fn extra_borders_need_a_shape_per_quadrant() {
+    let missing = TARGET.replace(r#", extra = ["box", "box"]"#, "");
+    let err = spec::parse_toml(&missing).err().unwrap();
+    assert!(
+        err.contains("one `extra` shape per extra border"),
+        "{}",
+        err
+    );
+    let negative = TARGET.replace("[30, 40]", "[30, -40]");
+    let err = spec::parse_toml(&negative).err().unwrap();
+    assert!(err.contains("must not be negative"), "{}", err);
+    for unordered in [
+        TARGET.replace("[30, 40]", "[40, 30]"),
+        TARGET.replace("[30, 40]", "[5, 40]"),
+        TARGET.replace("[30, 40]", "[20, 40]"),
+        include_str!("../figures.toml").replace("border_outer = 20", "border_outer = 10"),
+    ] {
+        let err = spec::parse_toml(&unordered).err().unwrap();
+        assert!(err.contains("borders must strictly increase"), "{}", err);
+    }
+}
+
+#[test]
+fn explain_reports_extra_rings() {
+    let target = &spec::parse_toml(TARGET).unwrap().figure[0];
+    let json = serde_json::to_vec(&explain::explain(target, -35, 0)).unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(body["arm"], "extra_inside");
+    assert_eq!(body["ring"], 3);
+    assert_eq!(body["relation"], "inside");
+    assert_eq!(body["extra"][0]["relation"], "outside");
+    assert_eq!(body["extra"][1]["border"], 40);
+    assert_eq!(body["extra"][1]["relation"], "inside");
+
+    let svg = render::svg(target);
+    assert!(svg.contains(r#"<mask id="mask-upper-right-3">"#));
+    assert_eq!(svg.matches(r#"class="border""#).count(), 16);
+}
+
+const SECTORS: &str = r#"
+[[figure]]
+name = "sectors"
+border_inner = 10
+border_outer = 20
+
+[[figure.sectors]]
+from = 0
+inner = "box"
+outer = "circle"
+
+[[figure.sectors]]
+from = [1, 2]
+inner = "circle"
+outer = "box"
+
+[[figure.sectors]]
+from = 180
+inner = "diamond"
+outer = "box"
+"#;
+
+#[test]
+fn rays_are_exact_directions() {
+    use sector::Ray;
+    assert_eq!(Ray::Degrees(0.0).direction(), (1, 0));
+    assert_eq!(Ray::Degrees(45.0).direction(), (1, 1));
+    assert_eq!(Ray::Degrees(-90.0).direction(), (0, -1));
+    assert_eq!(Ray::Degrees(540.0).direction(), (-1, 0));
+    assert_eq!(Ray::Degrees(-135.0).direction(), (-1, -1));
+    assert_eq!(Ray::Vector([4, 8]).direction(), (1, 2));
+}
+
+#[test]
+fn sectors_own_their_starting_ray() {
+    let figure = &spec::parse_toml(SECTORS).unwrap().figure[0];
+    let sector = |x: i128, y: i128| match figure.parts(x, y)[0].0 {
+        spec::Part::Sector(k) => k,
+        spec::Part::Quadrant(_) => unreachable!(),
+    };
+    assert_eq!(sector(0, 0), 0);
+    assert_eq!(sector(5, 0), 0);
+    assert_eq!(sector(5, 9), 0);
+    assert_eq!(sector(5, 10), 1);
+    assert_eq!(sector(5, 11), 1);
+    assert_eq!(sector(-5, 1), 1);
+    assert_eq!(sector(-5, 0), 2);
+    assert_eq!(sector(0, -5), 2);
+    assert_eq!(sector(5, -1), 2);
+
+    // (10, 20) is on the ray [1, 2] and so on the second sector's box; just
+    // below it the first sector's circle leaves it outside
+    assert_eq!(figure.locate(10, 20), figure::Relation::Border);
+    assert_eq!(figure.locate(10, 19), figure::Relation::Outside);
+    assert_eq!(
+        figure.locate_exact(Ratio::new(19, 2), Ratio::from(19)),
+        figure::Relation::Inside
+    );
+    assert_eq!(figure.locate(-10, 0), figure::Relation::Border);
+    assert_eq!(figure.locate(-5, 5), figure::Relation::Outside);
+}
+
+#[test]
+fn right_angle_sectors_match_quadrants_off_the_axes() {
+    let figures = figures();
+    let quarters = format!(
+        "[[figure]]\nname = \"quarters\"\nborder_inner = 10\nborder_outer = 20\n{}",
+        [
+            (0, "box", "circle"),
+            (90, "circle", "box"),
+            (180, "box", "box"),
+            (270, "circle", "circle"),
+        ]
+        .iter()
+        .map(|(from, inner, outer)| format!(
+            "[[figure.sectors]]\nfrom = {}\ninner = \"{}\"\nouter = \"{}\"\n",
+            from, inner, outer
+        ))
+        .collect::<String>()
+    );
+    let quarters = &spec::parse_toml(&quarters).unwrap().figure[0];
+    for x in (-99..100).filter(|&x| x != 0) {
+        for y in (-99..100).filter(|&y| y != 0) {
+            assert_eq!(quarters.locate(x, y), figures[0].locate(x, y));
+        }
+    }
+}
+
+#[test]
+fn figure_file_checks_sectors() {
+    let second = "from = [1, 2]";
+    for (replace, with, message) in [
+        (second, "from = 0", "distinct and go counter-clockwise"),
+        (second, "from = 270", "distinct and go counter-clockwise"),
+        (second, "from = [0, 0]", "non-zero vector"),
+        (second, "from = 30", "not a multiple of 45 degrees"),
+        (second, "from = [1, 2000000]", "at most 1000000"),
+        (
+            "outer = \"box\"",
+            "outer = \"box\"\ncolour = 1",
+            "unknown field `colour`",
+        ),
+        (
+            "border_outer = 20",
+            "border_outer = 20\n\n[figure.quadrants]\nupper_right = { inner = \"box\", outer = \"box\" }\nlower_right = { inner = \"box\", outer = \"box\" }\nupper_left = { inner = \"box\", outer = \"box\" }\nlower_left = { inner = \"box\", outer = \"box\" }\n",
+            "either `quadrants` or `sectors`",
+        ),
+    ] {
+        let err = spec::parse_toml(&SECTORS.replacen(replace, with, 1))
+            .err()
+            .unwrap();
+        assert!(err.contains(message), "{}: {}", with, err);
+    }
+    let none = SECTORS.split("[[figure.sectors]]").next().unwrap();
+    let err = spec::parse_toml(none).err().unwrap();
+    assert!(err.contains("either `quadrants` or `sectors`"), "{}", err);
+}
+
+#[test]
+fn explain_and_render_sectors() {
+    let figure = &spec::parse_toml(SECTORS).unwrap().figure[0];
+    let json = serde_json::to_vec(&explain::explain(figure, 8, 16)).unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(body["sector"], 1);
+    assert!(body.get("quadrant").is_none());
+    assert_eq!(body["inner"]["shape"], "circle");
+
+    let svg = render::svg(figure);
+    assert_eq!(svg.matches("<clipPath id=\"clip-sector-").count(), 3);
+    assert!(svg.contains(r#"<mask id="mask-sector-2">"#));
+}
+
+const AXES: &str = r#"
+[[figure]]
+name = "axes"
+border_inner = 10
+border_outer = 20
+
+[figure.quadrants]
+upper_right = { inner = "circle", outer = { ellipse = [2, 1] } }
+lower_right = { inner = "circle", outer = "circle" }
+upper_left = { inner = "circle", outer = { ellipse = [1, 2] } }
+lower_left = { inner = { polygon = [[1, 1], [2, 1], [2, 2]] }, outer = "circle" }
+"#;
+
+// Off the axes every rule agrees; on them the four quadrants above give
+// different answers, so each result shows which band was used.
+#[test]
+fn axes_resolve_every_axis_and_the_origin() {
+    use figure::Relation::*;
+    let axes = |rule: &str| {
+        let text = AXES.replace(
+            "border_outer = 20\n",
+            &format!("border_outer = 20\naxes = {}\n", rule),
+        );
+        spec::parse_toml(&text).unwrap().figure.remove(0)
+    };
+    let points = [
+        (25, 0),
+        (-25, 0),
+        (0, 25),
+        (0, -25),
+        (0, 0),
+        (40, 0),
+        (15, 15),
+    ];
+    for (rule, expected) in [
+        (
+            "\"sign\"",
+            [Outside, Outside, Inside, Outside, Inside, Outside, Inside],
+        ),
+        (
+            "{ quadrant = \"upper_right\" }",
+            [Inside, Inside, Outside, Outside, Outside, Border, Inside],
+        ),
+        (
+            "\"border\"",
+            [Border, Border, Border, Border, Border, Border, Inside],
+        ),
+        (
+            "\"union\"",
+            [Inside, Outside, Inside, Outside, Inside, Border, Inside],
+        ),
+    ] {
+        let figure = axes(rule);
+        for ((x, y), relation) in points.into_iter().zip(expected) {
+            assert_eq!(figure.locate(x, y), relation, "{} ({}, {})", rule, x, y);
+            assert_eq!(
+                figure.locate_f64(x.into(), y.into(), 1e-9),
+                relation,
+                "{} ({}, {})",
+                rule,
+                x,
+                y
+            );
+            let explanation = explain::explain(&figure, x, y);
+            assert_eq!(explanation.relation, relation);
+        }
+    }
+
+    let sign = &spec::parse_toml(AXES).unwrap().figure[0];
+    let default = axes("\"sign\"");
+    for x in -50..=50 {
+        for y in -50..=50 {
+            assert_eq!(sign.locate(x, y), default.locate(x, y));
+        }
+    }
+}
+
+#[test]
+fn axes_explain_and_check() {
+    let border = AXES.replace(
+        "border_outer = 20\n",
+        "border_outer = 20\naxes = \"border\"\n",
+    );
+    let figure = &spec::parse_toml(&border).unwrap().figure[0];
+    let json = serde_json::to_vec(&explain::explain(figure, 0, 7)).unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "figure": "axes",
+            "x": 0,
+            "y": 7,
+            "arm": "axis_border",
+            "relation": "border"
+        })
+    );
+
+    let union = AXES.replace(
+        "border_outer = 20\n",
+        "border_outer = 20\naxes = \"union\"\n",
+    );
+    let figure = &spec::parse_toml(&union).unwrap().figure[0];
+    let explanation = explain::explain(figure, 40, 0);
+    assert_eq!(explanation.arm, "outer_border");
+    assert_eq!(
+        serde_json::to_value(explanation.part).unwrap(),
+        serde_json::json!({"quadrant": "upper_right"})
+    );
+    assert_eq!(figure.metadata()["axes"], "union");
+    assert!(figures()[0].metadata().get("axes").is_none());
+
+    let sectors = SECTORS.replace(
+        "border_outer = 20\n",
+        "border_outer = 20\naxes = \"union\"\n",
+    );
+    let err = spec::parse_toml(&sectors).err().unwrap();
+    assert!(
+        err.contains("`axes` only applies to `quadrants`"),
+        "{}",
+        err
+    );
+}
+
+const FORMULAS: &str = r#"
+[[formula]]
+name = "figure-1-expr"
+expression = """
+x > 0
+    ? (y > 0 ? max(|x|, |y|) > 10 && x^2 + y^2 < 400 : x^2 + y^2 > 100 && x^2 + y^2 < 400)
+    : (y > 0 ? x^2 + y^2 > 100 && max(|x|, |y|) < 20 : max(|x|, |y|) > 10 && max(|x|, |y|) < 20)
+"""
+
+[[formula]]
+name = "figure-2-expr"
+expression = """
+x > 0 && y > 0
+    ? max(abs(x), abs(y)) > 20 && x*x + y*y < 1600
+    : x^2 + y^2 > 400 && max(|x|, |y|) < 40
+"""
+"#;
+
+#[test]
+fn formulas_reproduce_point_location() {
+    let registry = Registry::from(spec::parse_toml(FORMULAS).unwrap());
+    let (expr_1, expr_2) = (
+        registry.get("figure-1-expr").unwrap(),
+        registry.get("figure-2-expr").unwrap(),
+    );
+    for x in -99..100 {
+        for y in -99..100 {
+            assert_eq!(expr_1.locate(x, y), figure::point_location1(x, y));
+            assert_eq!(expr_2.locate(x, y), figure::point_location2(x, y));
+        }
+    }
+}
+
+#[test]
+fn formulas_take_the_border_from_equality() {
+    use figure::Relation::*;
+    let expr = |text: &str| expr::Expr::try_from(text.to_string()).unwrap();
+    let disc = expr("x^2 + y^2 < 100");
+    assert_eq!(disc.relation(6i128, 8), Border);
+    assert_eq!(disc.relation(6i128, 7), Inside);
+    assert_eq!(disc.relation(-6i128, -9), Outside);
+    assert_eq!(disc.relation(Ratio::new(15, 2), Ratio::new(-25, 4)), Inside);
+    assert_eq!(disc.relation(Ratio::new(14, 5), Ratio::new(-48, 5)), Border);
+
+    let ring = expr("!(x^2 + y^2 <= 25) && -(x + y) * 2 > -40 || min(x, y) >= 3");
+    assert_eq!(ring.relation(5i128, 0), Border);
+    assert_eq!(ring.relation(6i128, 0), Inside);
+    assert_eq!(ring.relation(10i128, 10), Inside);
+    assert_eq!(ring.relation(15i128, 5), Inside);
+    assert_eq!(ring.relation(30i128, 0), Outside);
+
+    // conditions hold or not, `>=` including equality
+    let choice = expr("x >= 0 ? x < 5 : x > -5");
+    assert_eq!(choice.relation(0i128, 0), Inside);
+    assert_eq!(choice.relation(5i128, 0), Border);
+    assert_eq!(choice.relation(-5i128, 0), Border);
+    assert_eq!(choice.relation(-6i128, 0), Outside);
+    let value = expr("(y > 0 ? y : -y) < 4");
+    assert_eq!(value.relation(0i128, -4), Border);
+}
+
+#[test]
+fn formulas_report_parse_errors() {
+    for (text, message) in [
+        ("x >", "unexpected end of expression"),
+        ("x + y", "expected a condition at 1, found a number"),
+        ("x > 0 && 3", "expected a condition at 10, found a number"),
+        ("x < y < 3", "comparisons do not chain, at 7"),
+        (
+            "(x > 0) + 1 > 0",
+            "expected a number at 1, found a condition",
+        ),
+        ("radius < 3", "unknown name `radius` at 1"),
+        ("x = 3", "unexpected `=` at 3"),
+        ("x ^ y > 0", "`^` needs a literal exponent, at 5"),
+        ("x^17 > 0", "exponents must be at most 16"),
+        ("x^", "`^` needs a literal exponent, at 3"),
+        ("(x > 0", "expected `)` at 7"),
+        ("abs(x, y) > 0", "`abs` takes one argument"),
+        (
+            "x > 0 ? y : y > 0",
+            "expected a number at 13, found a condition",
+        ),
+        ("x > 0 ? y : 1", "expected a condition at 1, found a number"),
+        ("x > 0 y > 0", "unexpected `y` at 7"),
+        (
+            "x > 99999999999999999999999999999999999999999",
+            "number too large at 5",
+        ),
+    ] {
+        let err = expr::Expr::try_from(text.to_string()).err().unwrap();
+        assert!(err.contains(message), "{}: {}", text, err);
+    }
+
+    let formula = |expression: &str| {
+        format!(
+            "[[formula]]\nname = \"f\"\nexpression = \"{}\"\n",
+            expression
+        )
+    };
+    let err = spec::parse_toml(&formula("x < ")).err().unwrap();
+    assert!(err.contains("unexpected end of expression"), "{}", err);
+    assert!(spec::parse_toml(&formula("x^16 > 0")).is_ok());
+    let err = spec::parse_toml(&formula("x^16 * x^16 > 0")).err().unwrap();
+    assert!(err.contains("overflows exact arithmetic"), "{}", err);
+    let clash = format!(
+        "{}{}",
+        FORMULAS,
+        formula("x > 0").replace("\"f\"", "\"figure-2-expr\"")
+    );
+    let err = spec::parse_toml(&clash).err().unwrap();
+    assert!(err.contains("duplicate figure name"), "{}", err);
+}
+
+#[tokio::test]
+async fn formula_routes() {
+    let registry = Arc::new(Registry::from(spec::parse_toml(FORMULAS).unwrap()));
+    let get = |path: &'static str| {
+        let registry = registry.clone();
+        async move {
+            let resp = request().path(path).reply(&routes(registry)).await;
+            String::from_utf8(resp.body().to_vec()).unwrap()
+        }
+    };
+    assert_eq!(get("/figure-1-expr?x=15&y=5").await, "inside");
+    assert_eq!(get("/figure/figure-2-expr?x=-40&y=7").await, "border");
+    let body: serde_json::Value = serde_json::from_str(&get("/figures").await).unwrap();
+    assert_eq!(body[1]["name"], "figure-2-expr");
+    assert!(body[1]["expression"]
+        .as_str()
+        .unwrap()
+        .starts_with("x > 0 && y > 0"));
+}
+
+#[tokio::test]
+async fn distance_route() {
+    let registry = registry();
+    let get = |path: &'static str| {
+        let registry = registry.clone();
+        async move {
+            let resp = request().path(path).reply(&routes(registry)).await;
+            let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+            (resp.status(), body)
+        }
+    };
+    // between the box of 10 and the circle of 20, the box is 2 away
+    let (status, body) = get("/figure-1/distance?x=12&y=9").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "figure": "figure-1",
+            "x": 12,
+            "y": 9,
+            "relation": "inside",
+            "distance": -2.0,
+            "squared": 4
+        })
+    );
+    let (_, body) = get("/figure-1/distance?x=0&y=0").await;
+    assert_eq!(
+        (body["distance"].as_f64(), body["squared"].as_i64()),
+        (Some(10.0), Some(100))
+    );
+    let (_, body) = get("/figure/figure-1/distance?x=-30&y=-30").await;
+    assert_eq!(body["squared"], 200);
+    assert!((body["distance"].as_f64().unwrap() - 200f64.sqrt()).abs() < 1e-12);
+    let (_, body) = get("/figure-1/distance?x=10&y=5").await;
+    assert_eq!(
+        (body["relation"].as_str(), body["distance"].as_f64()),
+        (Some("border"), Some(0.0))
+    );
+
+    // circles only have a float unless the radius through the point is whole
+    let (_, body) = get("/figure-1/distance?x=15&y=-5").await;
+    assert!(body.get("squared").is_none());
+    assert!((body["distance"].as_f64().unwrap() + 20.0 - 250f64.sqrt()).abs() < 1e-12);
+
+    let (status, body) = get("/figure-1/distance?x=3").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["field"], "y");
+    let (status, _) = get("/figure-1/distance?x=3&y=4&eps=1").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+const SEAMED: &str = r#"
+[[figure]]
+name = "seamed"
+border_inner = 20
+border_outer = 50
+
+[figure.quadrants]
+upper_right = { inner = { ellipse = [2, 1] }, outer = "circle" }
+lower_right = { inner = "box", outer = "box" }
+upper_left = { inner = { ellipse = [3, 1] }, outer = "circle" }
+lower_left = { inner = "box", outer = "box" }
+"#;
+
+#[test]
+fn distances_cross_into_other_parts() {
+    use figure::Relation::*;
+    let seamed = &spec::parse_toml(SEAMED).unwrap().figure[0];
+    // inside the ellipse the outer box of the lower right is a step away,
+    // across the axis
+    let below = distance::distance(seamed, 35, 1);
+    assert_eq!(below.relation, Outside);
+    assert!((below.distance - 1.0).abs() < 1e-9, "{}", below.distance);
+    let above = distance::distance(seamed, 35, -1);
+    assert_eq!(above.relation, Inside);
+    assert!((above.distance + 1.0).abs() < 1e-9, "{}", above.distance);
+    // the axis between the two is itself on the outline
+    assert!(distance::distance(seamed, 35, 0).distance.abs() < 1e-9);
+
+    // the ellipse reaches past the inner box of 20 only in its own quadrant
+    let right = distance::distance(seamed, 25, 3);
+    assert_eq!(right.relation, Outside);
+    assert!((right.distance - 3.0).abs() < 1e-9, "{}", right.distance);
+    // the circle of 50 runs inside the wider ellipse here, where it is no
+    // border
+    let hidden = distance::distance(seamed, -48, 10);
+    assert_eq!(hidden.relation, Outside);
+    let ellipse = distance::to_border_f64(&spec::Shape::Ellipse([3, 1]), -48.0, 10.0, 20.0);
+    assert!(ellipse > 1.5);
+    assert!(
+        (hidden.distance - ellipse).abs() < 1e-9,
+        "{}",
+        hidden.distance
+    );
+}
+
+#[test]
+fn nearest_points_across_parts() {
+    use spec::{Part, Quadrant};
+    let seamed = &spec::parse_toml(SEAMED).unwrap().figure[0];
+    // the axis between the ellipse and the boxes of the lower right
+    let seam = distance::nearest(seamed, 35, 1);
+    assert!(matches!(
+        seam.part,
+        Some(Part::Quadrant(Quadrant::LowerRight))
+    ));
+    assert!(seam.border.is_none() && seam.function.is_none());
+    assert_eq!(seam.point, [35.0, 0.0]);
+    assert_eq!(seam.distance, 1.0);
+    // the outer box of the lower right
+    let boxed = distance::nearest(seamed, 45, -30);
+    assert!(matches!(
+        boxed.part,
+        Some(Part::Quadrant(Quadrant::LowerRight))
+    ));
+    assert_eq!((boxed.border, boxed.function), (Some(1), Some("box_calc")));
+    assert_eq!(boxed.point, [50.0, -30.0]);
+}
+
+#[test]
+fn distances_to_shape_borders() {
+    use distance::{to_border, to_border_f64};
+    use spec::Shape::*;
+    let squared = |shape: &spec::Shape, x, y, border| {
+        to_border(shape, x, y, border)
+            .squared
+            .map(|s| s.to_string())
+    };
+    assert_eq!(squared(&Diamond, 15, 2, 10).as_deref(), Some("29"));
+    assert_eq!(squared(&Diamond, 8, 8, 10).as_deref(), Some("18"));
+    assert_eq!(squared(&Diamond, -2, 3, 10).as_deref(), Some("25/2"));
+    assert_eq!(squared(&Box, 13, -14, 10).as_deref(), Some("25"));
+    assert_eq!(squared(&Circle, 6, 8, 20).as_deref(), Some("100"));
+    assert_eq!(squared(&Circle, 6, 7, 20), None);
+    assert_eq!(squared(&RotatedBox([3, 4]), 0, 0, 5).as_deref(), Some("25"));
+    assert_eq!(squared(&RotatedBox([1, 1]), 0, 0, 5), None);
+    assert_eq!(squared(&Ellipse([2, 2]), 0, 0, 5).as_deref(), Some("100"));
+    let triangle: spec::Shape =
+        serde_json::from_str(r#"{"polygon": [[0, 0], [1, 0], [0, "1/2"]]}"#).unwrap();
+    assert_eq!(squared(&triangle, 10, 10, 10).as_deref(), Some("80"));
+    assert_eq!(squared(&triangle, 2, 1, 10).as_deref(), Some("1"));
+
+    for (shape, x, y, expected) in [
+        (Ellipse([2, 1]), 0.0, 0.0, 10.0),
+        (Ellipse([2, 1]), 30.0, 0.0, 10.0),
+        (Ellipse([1, 3]), 0.0, -35.0, 5.0),
+        (Lp(4), 20.0, 0.0, 10.0),
+        (Lp(4), 0.0, 4.0, 6.0),
+        (RotatedBox([1, 1]), 0.0, 0.0, 10.0),
+    ] {
+        let d = to_border_f64(&shape, x, y, 10.0);
+        assert!(
+            (d - expected).abs() < 1e-9,
+            "{} at ({}, {}): {}",
+            shape.function(),
+            x,
+            y,
+            d
+        );
+    }
+    // on the diagonal the nearest point of `x^4 + y^4 = 10^4` is the one
+    // with both coordinates `10 / 2^(1/4)`
+    let corner = to_border_f64(&Lp(4), 20.0, 20.0, 10.0);
+    assert!((corner - (20.0 * 2f64.sqrt() - 10.0 * 2f64.powf(0.25))).abs() < 1e-9);
+}
+
+proptest! {
+    #[test]
+    fn exact_distances_agree_with_floats(x in -60..60, y in -60..60, b in 1..40) {
+        use spec::Shape::*;
+        let triangle: spec::Shape =
+            serde_json::from_str(r#"{"polygon": [[0, 0], [1, 0], ["-1/3", "3/2"]]}"#).unwrap();
+        for shape in [Box, Diamond, Circle, Lp(1), Lp(2), RotatedBox([3, 4]), RotatedBox([-5, 12]), triangle] {
+            let exact = distance::to_border(&shape, x, y, b);
+            let float = distance::to_border_f64(&shape, x.into(), y.into(), b.into());
+            prop_assert!((exact.float - float).abs() < 1e-9, "{}: {} {}", shape.function(), exact.float, float);
+        }
+        let ellipse = distance::to_border_f64(&Ellipse([3, 3]), x.into(), y.into(), b.into());
+        let circle = distance::to_border_f64(&Circle, x.into(), y.into(), (3 * b).into());
+        prop_assert!((ellipse - circle).abs() < 1e-6);
+    }
+}
+
+#[tokio::test]
+async fn nearest_route() {
+    let registry = registry();
+    let get = |path: &'static str| {
+        let registry = registry.clone();
+        async move {
+            let resp = request().path(path).reply(&routes(registry)).await;
+            let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+            (resp.status(), body)
+        }
+    };
+    // the box of 10 is nearer than the circle of 20
+    let (status, body) = get("/figure-1/nearest?x=12&y=9").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "figure": "figure-1",
+            "x": 12,
+            "y": 9,
+            "quadrant": "upper_right",
+            "border": 0,
+            "function": "box_calc",
+            "point": [10.0, 9.0],
+            "distance": 2.0
+        })
+    );
+    let (_, body) = get("/figure/figure-1/nearest?x=17&y=0").await;
+    assert_eq!(
+        (body["border"].as_u64(), body["point"].clone()),
+        (Some(1), serde_json::json!([20.0, 0.0]))
+    );
+    let (_, body) = get("/figure-1/nearest?x=15&y=-5").await;
+    let point = body["point"].as_array().unwrap();
+    let (px, py) = (point[0].as_f64().unwrap(), point[1].as_f64().unwrap());
+    assert!((px.hypot(py) - 20.0).abs() < 1e-9);
+    assert!((px + 3.0 * py).abs() < 1e-9);
+
+    // from the origin the nearest point is in the quadrant it belongs to,
+    // not in the one the origin is classified in
+    let (_, body) = get("/figure-1/nearest?x=0&y=0").await;
+    assert_eq!(
+        (
+            &body["quadrant"],
+            &body["border"],
+            &body["point"],
+            &body["distance"]
+        ),
+        (
+            &serde_json::json!("lower_right"),
+            &serde_json::json!(0),
+            &serde_json::json!([10.0, 0.0]),
+            &serde_json::json!(10.0)
+        )
+    );
+
+    let (status, body) = get("/figure-1/nearest?y=3").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["field"], "x");
+}
+
+#[test]
+fn nearest_points_on_shape_borders() {
+    use distance::{nearest_f64, to_border_f64};
+    use spec::Shape::*;
+    assert_eq!(nearest_f64(&Box, 3.0, -2.0, 10.0), (10.0, -2.0));
+    assert_eq!(nearest_f64(&Box, 13.0, -14.0, 10.0), (10.0, -10.0));
+    assert_eq!(nearest_f64(&Circle, 0.0, 0.0, 5.0), (5.0, 0.0));
+    assert_eq!(nearest_f64(&Circle, -6.0, 8.0, 20.0), (-12.0, 16.0));
+    assert_eq!(nearest_f64(&Diamond, -15.0, 2.0, 10.0), (-10.0, 0.0));
+    assert_eq!(nearest_f64(&Diamond, 8.0, -8.0, 10.0), (5.0, -5.0));
+    let (x, y) = nearest_f64(&RotatedBox([1, 1]), 20.0, 0.0, 10.0);
+    assert!((x - 10.0 * 2f64.sqrt()).abs() < 1e-9 && y.abs() < 1e-9);
+
+    // the nearest point is as far away as the border is
+    let triangle: spec::Shape =
+        serde_json::from_str(r#"{"polygon": [[0, 0], [1, 0], [0, "1/2"]]}"#).unwrap();
+    for shape in [
+        Box,
+        Diamond,
+        Circle,
+        Lp(4),
+        Ellipse([2, 1]),
+        RotatedBox([3, 4]),
+        triangle,
+    ] {
+        for (x, y) in [(0.0, 0.0), (13.0, -4.0), (-2.0, 7.0), (25.0, 25.0)] {
+            let (nx, ny) = nearest_f64(&shape, x, y, 10.0);
+            let d = to_border_f64(&shape, x, y, 10.0);
+            assert!(
+                ((x - nx).hypot(y - ny) - d).abs() < 1e-9,
+                "{} at ({}, {})",
+                shape.function(),
+                x,
+                y
+            );
+        }
+    }
+}
+
+#[test]
+fn path_crossings_of_large_borders() {
+    let large = spec::parse_toml(
+        r#"
+[[figure]]
+name = "large"
+border_inner = 100000000
+border_outer = 200000000
+
+[figure.range]
+x = {}
+y = {}
+
+[figure.quadrants]
+upper_right = { inner = "circle", outer = "circle" }
+lower_right = { inner = "circle", outer = "circle" }
+upper_left = { inner = "circle", outer = "circle" }
+lower_left = { inner = "circle", outer = "circle" }
+"#,
+    )
+    .unwrap();
+    let points = [
+        figure::MyPoint {
+            x: -300000000,
+            y: 7,
+        },
+        figure::MyPoint { x: 300000000, y: 7 },
+    ];
+    let report = path::path(&large.figure[0], &points);
+    // the circles are crossed at irrational points, each on its border
+    let json = serde_json::to_value(&report).unwrap();
+    let crossings = json["crossings"].as_array().unwrap();
+    assert_eq!(crossings.len(), 4);
+    for crossing in crossings {
+        assert_eq!(crossing["relation"], "border", "{}", crossing);
+    }
+}
+
+#[tokio::test]
+async fn path_route() {
+    let registry = registry();
+    let post = |path: &'static str, body: serde_json::Value| {
+        let registry = registry.clone();
+        async move {
+            let resp = request()
+                .method("POST")
+                .path(path)
+                .json(&body)
+                .reply(&routes(registry))
+                .await;
+            let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+            (resp.status(), body)
+        }
+    };
+    // through the box of 20 and the circle of 10 on the left, then the box of
+    // 10 and the circle of 20 on the right; the circles are crossed at
+    // irrational points
+    let (status, body) = post(
+        "/figure-1/path",
+        serde_json::json!([{"x": -30, "y": 5}, {"x": 30, "y": 5}]),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["figure"], "figure-1");
+    let segments = body["segments"].as_array().unwrap();
+    let relations: Vec<_> = segments.iter().map(|s| s["relation"].clone()).collect();
+    assert_eq!(
+        relations,
+        ["outside", "inside", "outside", "inside", "outside"]
+    );
+    assert_eq!(segments[0]["from"], serde_json::json!([-30, 5]));
+    assert_eq!(segments[0]["to"], serde_json::json!([-20, 5]));
+    assert_eq!(segments[2]["to"], serde_json::json!([10, 5]));
+    let crossings = body["crossings"].as_array().unwrap();
+    assert_eq!(crossings.len(), 4);
+    assert_eq!(
+        crossings[0],
+        serde_json::json!({
+            "leg": 0,
+            "point": [-20, 5],
+            "relation": "border",
+            "before": "outside",
+            "after": "inside"
+        })
+    );
+    let x = crossings[1]["point"][0].as_f64().unwrap();
+    assert!((x + 75f64.sqrt()).abs() < 1e-9);
+    assert_eq!(
+        (
+            crossings[1]["before"].as_str(),
+            crossings[1]["after"].as_str()
+        ),
+        (Some("inside"), Some("outside"))
+    );
+    let x = crossings[3]["point"][0].as_f64().unwrap();
+    assert!((x - 375f64.sqrt()).abs() < 1e-9);
+
+    // box crossings at fractions, a circle crossed at a lattice point and a
+    // vertex that changes nothing
+    let (_, body) = post(
+        "/figure/figure-1/path",
+        serde_json::json!([{"x": 1, "y": 1}, {"x": 13, "y": 2}]),
+    )
+    .await;
+    assert_eq!(
+        body["crossings"][0]["point"],
+        serde_json::json!([10, "7/4"])
+    );
+    let (_, body) = post(
+        "/figure-1/path",
+        serde_json::json!([{"x": 8, "y": -6}, {"x": 24, "y": -18}, {"x": 24, "y": -18}, {"x": 30, "y": -18}]),
+    )
+    .await;
+    assert_eq!(
+        body["segments"],
+        serde_json::json!([
+            {"leg": 0, "from": [8, -6], "to": [16, -12], "relation": "inside"},
+            {"leg": 0, "from": [16, -12], "to": [24, -18], "relation": "outside"},
+            {"leg": 2, "from": [24, -18], "to": [30, -18], "relation": "outside"}
+        ])
+    );
+    assert_eq!(body["crossings"].as_array().unwrap().len(), 1);
+
+    // running along the border of the box
+    let (_, body) = post(
+        "/figure-1/path",
+        serde_json::json!([{"x": 10, "y": 2}, {"x": 10, "y": 8}]),
+    )
+    .await;
+    assert_eq!(body["segments"][0]["relation"], "border");
+    assert_eq!(body["crossings"], serde_json::json!([]));
+
+    let (status, body) = post("/figure-1/path", serde_json::json!([{"x": 1, "y": 1}])).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["field"], "[1]");
+    let (status, body) = post(
+        "/figure-1/path",
+        serde_json::json!([{"x": 1, "y": 1}, {"x": 1, "y": 100}]),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        (body["error"].as_str(), body["field"].as_str()),
+        (Some("out_of_range"), Some("[1].y"))
+    );
+}
+
+fn path_t(point: &path::PathPoint, from: (i32, i32), to: (i32, i32)) -> f64 {
+    let [x, y] = match point {
+        path::PathPoint::Exact(p) => p.map(|c| c.num() as f64 / c.den() as f64),
+        path::PathPoint::Float(p) => *p,
+    };
+    let (dx, dy) = (f64::from(to.0 - from.0), f64::from(to.1 - from.1));
+    ((x - f64::from(from.0)) * dx + (y - f64::from(from.1)) * dy) / (dx * dx + dy * dy)
+}
+
+proptest! {
+    #[test]
+    fn path_segments_agree_with_locate(
+        x0 in -50..50, y0 in -50..50, x1 in -50..50, y1 in -50..50, figure in 0..2usize
+    ) {
+        prop_assume!((x0, y0) != (x1, y1));
+        let spec = &figures()[figure];
+        let points = [figure::MyPoint { x: x0, y: y0 }, figure::MyPoint { x: x1, y: y1 }];
+        let report = path::path(spec, &points);
+        for k in 0..=64 {
+            let t = Ratio::new(k, 64);
+            let at = |a: i32, b: i32| Ratio::from(a) + t * Ratio::from(b - a);
+            let relation = spec.locate_exact(at(x0, x1), at(y0, y1));
+            let t = k as f64 / 64.0;
+            let holder = report.segments.iter().find(|s| {
+                let (from, to) = (
+                    path_t(&s.from, (x0, y0), (x1, y1)),
+                    path_t(&s.to, (x0, y0), (x1, y1)),
+                );
+                from + 1e-9 < t && t < to - 1e-9
+            });
+            if let Some(segment) = holder {
+                prop_assert_eq!(segment.relation, relation);
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn stats_of_unbounded_ranges() {
+    let registry = Arc::new(Registry::from(spec::parse_toml(UNBOUNDED).unwrap()));
+    let resp = request().path("/huge/stats").reply(&routes(registry)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    // the range has 2^64 points, too many to count
+    assert!(body.get("lattice").is_none());
+    let length = body["border_length"]["value"].as_f64().unwrap();
+    assert!(length.is_finite() && length > 0.0);
+    let area = body["area"]["value"].as_f64().unwrap();
+    assert!(area.is_finite() && area > 0.0);
+}
+
+#[tokio::test]
+async fn stats_route() {
+    let resp = request()
+        .path("/figure-1/stats")
+        .reply(&routes(registry()))
+        .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+    // 300 + 75π + (400 - 25π) + (100π - 100) over the quadrants
+    assert_eq!(
+        body["area"]["exact"],
+        serde_json::json!({"rational": 600, "pi": 150})
+    );
+    let area = body["area"]["value"].as_f64().unwrap();
+    assert!((area - (600.0 + 150.0 * std::f64::consts::PI)).abs() < 1e-9);
+    assert_eq!(
+        body["border_length"]["exact"],
+        serde_json::json!({"rational": 120, "pi": 30})
+    );
+
+    let mut counts = [0u64; 3];
+    for x in -99..100 {
+        for y in -99..100 {
+            counts[match figure::point_location1(x, y) {
+                figure::Relation::Inside => 0,
+                figure::Relation::Border => 1,
+                figure::Relation::Outside => 2,
+            }] += 1;
+        }
+    }
+    assert_eq!(
+        body["lattice"],
+        serde_json::json!({"inside": counts[0], "border": counts[1], "outside": counts[2]})
+    );
+
+    let resp = request()
+        .path("/figure/figure-1/stats")
+        .reply(&routes(registry()))
+        .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[test]
+fn stats_fall_back_to_approximations() {
+    use std::f64::consts::PI;
+    let close = |measure: &stats::Measure, expected: f64| {
+        assert!(measure.exact.is_none());
+        assert!(
+            (measure.value - expected).abs() < 1e-3 * expected,
+            "{} != {}",
+            measure.value,
+            expected
+        );
+    };
+
+    // figure-1 again, laid out as sectors
+    let sectors = r#"
+[[figure]]
+name = "sectors"
+border_inner = 10
+border_outer = 20
+
+[[figure.sectors]]
+from = 0
+inner = "box"
+outer = "circle"
+
+[[figure.sectors]]
+from = 90
+inner = "circle"
+outer = "box"
+
+[[figure.sectors]]
+from = 180
+inner = "box"
+outer = "box"
+
+[[figure.sectors]]
+from = [0, -1]
+inner = "circle"
+outer = "circle"
+"#;
+    let figure = &spec::parse_toml(sectors).unwrap().figure[0];
+    let stats = stats::stats(figure);
+    close(stats.area.as_ref().unwrap(), 600.0 + 150.0 * PI);
+    close(&stats.border_length, 120.0 + 30.0 * PI);
+
+    // the square is a polygon, which has no closed form
+    let polygon = r#"
+[[figure]]
+name = "polygon"
+border_inner = 10
+border_outer = 20
+
+[figure.quadrants]
+upper_right = { inner = "circle", outer = "box" }
+lower_right = { inner = "circle", outer = "box" }
+upper_left = { inner = { polygon = [[1, 1], [-1, 1], [-1, -1], [1, -1]] }, outer = "circle" }
+lower_left = { inner = { polygon = [[1, 1], [-1, 1], [-1, -1], [1, -1]] }, outer = "circle" }
+"#;
+    let figure = &spec::parse_toml(polygon).unwrap().figure[0];
+    let stats = stats::stats(figure);
+    assert!(stats.area.as_ref().unwrap().exact.is_none());
+    close(stats.area.as_ref().unwrap(), 600.0 + 150.0 * PI);
+    close(&stats.border_length, 120.0 + 30.0 * PI);
+
+    // diamonds have an exact area but not an exact border length
+    let target = &spec::parse_toml(TARGET).unwrap().figure[0];
+    let stats = stats::stats(target);
+    let exact = stats.area.as_ref().unwrap().exact.unwrap();
+    assert_eq!(
+        (exact.rational, exact.pi),
+        (Ratio::from(3500), Ratio::from(0))
+    );
+    close(
+        &stats.border_length,
+        320.0 + 70.0 * PI + 100.0 * 2f64.sqrt(),
+    );
+
+    let odd = TARGET
+        .replace("[30, 40]", "[30]")
+        .replace(r#", "circle"]"#, "]");
+    let odd = odd
+        .replace(r#", "box"]"#, "]")
+        .replace(r#", "diamond"]"#, "]");
+    let figure = &spec::parse_toml(&odd).unwrap().figure[0];
+    assert!(stats::stats(figure).area.is_none());
 }